@@ -0,0 +1,133 @@
+use crate::mips::Instr;
+use anyhow::{Result, bail};
+
+/// The table and row functions of a generated `.s` file
+pub struct Source {
+    /// Labels exported in the table, with the index of the word they point at
+    pub labels: Vec<(usize, String)>,
+    /// One-line comments in the table, with the index of the word they come before
+    pub comments: Vec<(usize, String)>,
+    pub table: Vec<String>,
+    /// Row functions in the order they appear; `row_end` has no body
+    pub functions: Vec<(String, Vec<Instr>)>,
+    /// Other functions, such as the C entry points, with the lines of their bodies
+    pub other: Vec<(String, Vec<String>)>,
+    /// Padding words between or after the row functions, with the number of functions before
+    /// each
+    pub padding: Vec<(usize, u32)>,
+}
+
+fn macro_arg<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+        .map(str::trim)
+}
+
+pub fn parse(text: &str) -> Result<Source> {
+    let mut labels = vec![];
+    let mut comments = vec![];
+    let mut table = vec![];
+    let mut functions = vec![];
+    let mut other = vec![];
+    let mut padding = vec![];
+    let mut current: Option<(String, Vec<Instr>)> = None;
+    let mut lines = vec![];
+    let mut skip = false;
+    let mut comment = false;
+
+    for (number, line) in text.lines().enumerate() {
+        let mut line = line.trim();
+
+        // only whole-line block comments are ever generated
+        if comment {
+            comment = !line.contains("*/");
+            continue;
+        }
+        if let Some(text) = line.strip_prefix("/*") {
+            comment = !line.contains("*/");
+            if let Some(text) = text.strip_suffix("*/")
+                && current.is_none()
+                && functions.is_empty()
+            {
+                comments.push((table.len(), text.trim().to_string()));
+            }
+            continue;
+        }
+        if let Some((code, _)) = line.split_once('#')
+            && !line.starts_with("#include")
+        {
+            line = code.trim();
+        }
+
+        if line.is_empty() || line.starts_with("#include") || line.starts_with(".set") {
+            continue;
+        }
+
+        let context = || format!("line {}", number + 1);
+
+        if let Some(name) = macro_arg(line, "LEAF") {
+            if let Some((open, _)) = &current {
+                bail!("{}: LEAF({name}) inside {open}", context());
+            }
+            current = Some((name.to_string(), vec![]));
+            lines.clear();
+        } else if let Some(name) = macro_arg(line, "END") {
+            match current.take() {
+                Some((open, _)) if open == name && skip => {
+                    other.push((open, std::mem::take(&mut lines)))
+                }
+                Some((open, body)) if open == name => functions.push((open, body)),
+                _ => bail!("{}: END({name}) without a matching LEAF", context()),
+            }
+            skip = false;
+        } else if let Some((name, body)) = &mut current {
+            lines.push(line.to_string());
+            if name == "row_end" || skip {
+                continue;
+            }
+            let Some(instr) = Instr::parse(line) else {
+                if name.starts_with("row_") {
+                    bail!("{}: can't parse {line:?} in {name}", context());
+                }
+                skip = true;
+                continue;
+            };
+            body.push(instr);
+        } else if let Some(label) = macro_arg(line, "EXPORT") {
+            labels.push((table.len(), label.to_string()));
+        } else if let Some(word) = line.strip_prefix(".word")
+            && !(functions.is_empty() && other.is_empty())
+        {
+            // padding between or after the functions, from --extra-layout
+            let word = word.trim();
+            let Some(word) = word
+                .strip_prefix("0x")
+                .and_then(|w| u32::from_str_radix(w, 16).ok())
+            else {
+                bail!("{}: can't parse padding word {word:?}", context());
+            };
+            padding.push((functions.len(), word));
+        } else if let Some(entry) = line.strip_prefix(".word") {
+            table.push(entry.trim().to_string());
+        } else if line.starts_with(".incbin") {
+            bail!("{}: sources built with --incbin can't be parsed", context());
+        } else {
+            bail!("{}: unexpected {line:?}", context());
+        }
+    }
+
+    if let Some((name, _)) = current {
+        bail!("{name} is never closed with END");
+    }
+
+    Ok(Source {
+        labels,
+        comments,
+        table,
+        functions,
+        other,
+        padding,
+    })
+}
@@ -0,0 +1,216 @@
+use crate::charmap::Charmap;
+use anyhow::{Context, Result, bail};
+
+/// A glyph being read, until its bitmap is complete
+struct Glyph {
+    name: String,
+    encoding: Option<usize>,
+    /// Width, height and offsets from the origin
+    bbx: Option<[i32; 4]>,
+    /// Bits of each bitmap row, leftmost first
+    rows: Option<Vec<Vec<bool>>>,
+}
+
+fn numbers<const N: usize>(args: &str) -> Option<[i32; N]> {
+    let numbers = args
+        .split_whitespace()
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<_>>>()?;
+    numbers.get(..N)?.try_into().ok()
+}
+
+/// The glyphs of a BDF font by encoding, each placed in a `glyph_width`x`glyph_height` cell
+/// whose corner is that of the font's bounding box, and the names of the glyphs that have set
+/// pixels outside their cell, which are left out; unencoded glyphs are ignored
+pub fn decode(
+    text: &str,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Result<(Vec<u8>, Vec<String>)> {
+    let cell = glyph_width * glyph_height;
+    let mut font: Option<[i32; 4]> = None;
+    let mut glyph: Option<Glyph> = None;
+    let mut glyphs = vec![];
+    let mut skipped = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let context = || format!("line {}", number + 1);
+        let line = line.trim();
+        let (keyword, args) = line.split_once(' ').unwrap_or((line, ""));
+
+        if let Some(Glyph {
+            rows: Some(rows), ..
+        }) = &mut glyph
+            && keyword != "ENDCHAR"
+        {
+            let row = line
+                .chars()
+                .map(|c| c.to_digit(16))
+                .collect::<Option<Vec<_>>>()
+                .with_context(|| format!("{}: bad bitmap row {line:?}", context()))?;
+            rows.push(
+                row.iter()
+                    .flat_map(|digit| (0..4).rev().map(move |bit| digit >> bit & 1 != 0))
+                    .collect(),
+            );
+            continue;
+        }
+
+        match keyword {
+            "FONTBOUNDINGBOX" => {
+                font = Some(
+                    numbers(args).with_context(|| format!("{}: bad bounding box", context()))?,
+                )
+            }
+            "STARTCHAR" => {
+                glyph = Some(Glyph {
+                    name: args.to_string(),
+                    encoding: None,
+                    bbx: None,
+                    rows: None,
+                })
+            }
+            "ENCODING" | "BBX" | "BITMAP" | "ENDCHAR" => {
+                let Some(glyph) = &mut glyph else {
+                    bail!("{}: {keyword} outside a glyph", context());
+                };
+                match keyword {
+                    "ENCODING" => {
+                        let [encoding] = numbers(args)
+                            .with_context(|| format!("{}: bad encoding", context()))?;
+                        glyph.encoding = usize::try_from(encoding).ok();
+                    }
+                    "BBX" => {
+                        glyph.bbx =
+                            Some(numbers(args).with_context(|| format!("{}: bad BBX", context()))?)
+                    }
+                    "BITMAP" => glyph.rows = Some(vec![]),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        if keyword != "ENDCHAR" {
+            continue;
+        }
+        let Some(Glyph {
+            name,
+            encoding,
+            bbx,
+            rows,
+        }) = glyph.take()
+        else {
+            unreachable!();
+        };
+        let Some(encoding) = encoding else {
+            continue;
+        };
+        let Some([font_width, font_height, font_x, font_y]) = font else {
+            bail!("{}: glyph {name} comes before FONTBOUNDINGBOX", context());
+        };
+        let [width, height, x, y] = bbx.unwrap_or([font_width, font_height, font_x, font_y]);
+        let rows = rows.unwrap_or_default();
+
+        // the bitmap's top left corner, in cell coordinates
+        let left = x - font_x;
+        let top = (font_y + font_height) - (y + height);
+
+        let mut pixels = vec![0; cell];
+        let mut fits = true;
+        for (row, bits) in rows.iter().take(height.max(0) as usize).enumerate() {
+            // the bits past the width only pad the row to whole bytes
+            for (column, _) in bits
+                .iter()
+                .take(width.max(0) as usize)
+                .enumerate()
+                .filter(|(_, set)| **set)
+            {
+                let (px, py) = (left + column as i32, top + row as i32);
+                if !(0..glyph_width as i32).contains(&px) || !(0..glyph_height as i32).contains(&py)
+                {
+                    fits = false;
+                    continue;
+                }
+                pixels[py as usize * glyph_width + px as usize] = 0xFF;
+            }
+        }
+
+        if !fits {
+            skipped.push(name);
+            continue;
+        }
+        if glyphs.len() < (encoding + 1) * cell {
+            glyphs.resize((encoding + 1) * cell, 0);
+        }
+        glyphs[encoding * cell..][..cell].copy_from_slice(&pixels);
+    }
+
+    if glyph.is_some() {
+        bail!("the last glyph is never closed with ENDCHAR");
+    }
+    if glyphs.is_empty() {
+        bail!("the font has no encoded glyphs that fit {glyph_width}x{glyph_height}");
+    }
+
+    Ok((glyphs, skipped))
+}
+
+/// A BDF font of `glyph_width`x`glyph_height` glyphs sitting on the baseline, encoded by
+/// `charmap`; glyphs it has no character for are unencoded
+pub fn encode(
+    glyphs: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+    charmap: &Charmap,
+    name: &str,
+) -> String {
+    let glyphs = glyphs
+        .chunks_exact(glyph_width * glyph_height)
+        .collect::<Vec<_>>();
+    // the advance in thousandths of the point size, at 75 dpi
+    let swidth = glyph_width * 1000 * 72 / (glyph_height * 75);
+
+    let mut rv = String::new();
+    rv += "STARTFONT 2.1\n";
+    rv += &format!("FONT {name}\n");
+    rv += &format!("SIZE {glyph_height} 75 75\n");
+    rv += &format!("FONTBOUNDINGBOX {glyph_width} {glyph_height} 0 0\n");
+    rv += "STARTPROPERTIES 2\n";
+    rv += &format!("FONT_ASCENT {glyph_height}\n");
+    rv += "FONT_DESCENT 0\n";
+    rv += "ENDPROPERTIES\n";
+    rv += &format!("CHARS {}\n", glyphs.len());
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        match charmap.char(index) {
+            Some(c) => {
+                rv += &format!("STARTCHAR uni{:04X}\n", c as u32);
+                rv += &format!("ENCODING {}\n", c as u32);
+            }
+            None => {
+                rv += &format!("STARTCHAR glyph{index:02X}\n");
+                rv += "ENCODING -1\n";
+            }
+        }
+        rv += &format!("SWIDTH {swidth} 0\n");
+        rv += &format!("DWIDTH {glyph_width} 0\n");
+        rv += &format!("BBX {glyph_width} {glyph_height} 0 0\n");
+        rv += "BITMAP\n";
+        for row in glyph.chunks_exact(glyph_width) {
+            for byte in row.chunks(8) {
+                let bits = byte
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |b, (i, &p)| b | ((p != 0) as u8) << (7 - i));
+                rv += &format!("{bits:02X}");
+            }
+            rv += "\n";
+        }
+        rv += "ENDCHAR\n";
+    }
+
+    rv += "ENDFONT\n";
+
+    rv
+}
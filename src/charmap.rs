@@ -0,0 +1,122 @@
+use anyhow::{Context, Result, bail};
+
+/// Mapping between glyph indices and the characters they draw
+pub struct Charmap {
+    entries: Vec<(usize, char)>,
+}
+
+impl Charmap {
+    /// Glyph index equals the character's code point
+    pub fn identity() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// One `<index> <char>` pair per line, where the character is either a literal or
+    /// `U+XXXX`; `#` starts a comment
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let context = || format!("charmap line {}", number + 1);
+
+            let Some((index, c)) = line.split_once(char::is_whitespace) else {
+                bail!("{}: expected `<index> <char>`", context());
+            };
+
+            let index = match index.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16),
+                None => index.parse(),
+            }
+            .with_context(context)?;
+
+            let c = c.trim();
+            let c = match c.strip_prefix("U+") {
+                Some(hex) if !hex.is_empty() => char::from_u32(u32::from_str_radix(hex, 16)?),
+                _ => {
+                    let mut chars = c.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => None,
+                    }
+                }
+            };
+            let Some(c) = c else {
+                bail!("{}: expected a single character or U+XXXX", context());
+            };
+
+            entries.push((index, c));
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn from_entries(entries: Vec<(usize, char)>) -> Self {
+        Self { entries }
+    }
+
+    /// The charmap as [`Charmap::parse`] reads it, in index order
+    pub fn source(&self) -> String {
+        let mut entries = self.entries.clone();
+        entries.sort();
+
+        let mut rv = String::new();
+        for (index, c) in entries {
+            if c.is_whitespace() || c.is_control() {
+                rv += &format!("0x{index:02X} U+{:04X}\n", c as u32);
+            } else {
+                rv += &format!("0x{index:02X} {c}\n");
+            }
+        }
+
+        rv
+    }
+
+    pub fn char(&self, index: usize) -> Option<char> {
+        if self.entries.is_empty() {
+            return char::from_u32(index as u32);
+        }
+
+        self.entries
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, c)| *c)
+    }
+
+    /// The characters of the `count` glyphs from `first` on, numbered from 0
+    pub fn range(&self, first: usize, count: usize) -> Self {
+        let entries = (0..count)
+            .filter_map(|index| Some((index, self.char(first + index)?)))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn glyph(&self, c: char) -> Option<usize> {
+        if self.entries.is_empty() {
+            return Some(c as usize);
+        }
+
+        self.entries.iter().find(|(_, e)| *e == c).map(|(i, _)| *i)
+    }
+}
+
+/// Turn a character into something usable in an assembler identifier
+pub fn sanitize(c: char) -> String {
+    if c.is_ascii_alphanumeric() || c == '_' {
+        c.to_string()
+    } else {
+        format!("u{:04X}", c as u32)
+    }
+}
+
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || "_.$".contains(c))
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_.$".contains(c))
+}
@@ -0,0 +1,296 @@
+use anyhow::{Context, Result, bail};
+use byteorder::{BE, ByteOrder, WriteBytesExt};
+
+/// A global symbol in the `.text` section
+pub struct Symbol {
+    pub name: String,
+    /// Offset into the section
+    pub offset: u32,
+    /// Bytes the symbol covers, or 0 for a bare label
+    pub size: u32,
+    /// Whether the symbol is code rather than data
+    pub function: bool,
+}
+
+const HEADER_SIZE: usize = 52;
+const SECTION_HEADER_SIZE: u16 = 40;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+
+const SHF_ALLOC: u32 = 0x2;
+const SHF_EXECINSTR: u32 = 0x4;
+const SHF_INFO_LINK: u32 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+const SHN_LORESERVE: u16 = 0xFF00;
+
+const ET_REL: u16 = 1;
+
+const R_MIPS_32: u32 = 2;
+
+/// ARCH_3 | 32BITMODE | ABI_O32 | NOREORDER, as the VR4300 toolchains write them
+const E_FLAGS: u32 = 0x2000_1101;
+
+/// Section indices, in the order the headers are written
+const TEXT: u16 = 1;
+const SYMTAB: u32 = 3;
+const STRTAB: u32 = 4;
+const SHSTRTAB: u16 = 5;
+
+/// Append `name` and its terminator to a string table, returning its offset
+fn intern(table: &mut Vec<u8>, name: &str) -> u32 {
+    let rv = table.len() as u32;
+    table.extend(name.as_bytes());
+    table.push(0);
+    rv
+}
+
+fn align(bin: &mut Vec<u8>, to: usize) {
+    bin.resize(bin.len().next_multiple_of(to), 0);
+}
+
+/// A big-endian MIPS relocatable object with `text` as its `.text` section, the words at each of
+/// `relocations` being offsets into it that the linker makes absolute
+pub fn relocatable(text: &[u8], relocations: &[u32], symbols: &[Symbol]) -> Vec<u8> {
+    let mut shstrtab = vec![0];
+    let mut strtab = vec![0];
+
+    // the null symbol, then the section symbol the relocations are against
+    let mut symtab = vec![0; 16];
+    symtab.write_u32::<BE>(0).unwrap();
+    symtab.write_u32::<BE>(0).unwrap();
+    symtab.write_u32::<BE>(0).unwrap();
+    symtab.push(STB_LOCAL << 4 | STT_SECTION);
+    symtab.push(0);
+    symtab.write_u16::<BE>(TEXT).unwrap();
+    let locals = 2;
+
+    for symbol in symbols {
+        let kind = if symbol.function {
+            STT_FUNC
+        } else {
+            STT_OBJECT
+        };
+        symtab
+            .write_u32::<BE>(intern(&mut strtab, &symbol.name))
+            .unwrap();
+        symtab.write_u32::<BE>(symbol.offset).unwrap();
+        symtab.write_u32::<BE>(symbol.size).unwrap();
+        symtab.push(STB_GLOBAL << 4 | kind);
+        symtab.push(0);
+        symtab.write_u16::<BE>(TEXT).unwrap();
+    }
+
+    let mut rel = vec![];
+    for &offset in relocations {
+        rel.write_u32::<BE>(offset).unwrap();
+        rel.write_u32::<BE>(1 << 8 | R_MIPS_32).unwrap();
+    }
+
+    // name, type, flags, link, info, alignment, entry size and contents of each section
+    let sections = [
+        (
+            ".text",
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            0,
+            0,
+            16,
+            0,
+            text,
+        ),
+        (
+            ".rel.text",
+            SHT_REL,
+            SHF_INFO_LINK,
+            SYMTAB,
+            TEXT as u32,
+            4,
+            8,
+            &rel,
+        ),
+        (".symtab", SHT_SYMTAB, 0, STRTAB, locals, 4, 16, &symtab),
+        (".strtab", SHT_STRTAB, 0, 0, 0, 1, 0, &strtab),
+    ];
+    let names = sections
+        .iter()
+        .map(|(name, ..)| intern(&mut shstrtab, name))
+        .collect::<Vec<_>>();
+    let shstrtab_name = intern(&mut shstrtab, ".shstrtab");
+
+    let mut body = vec![0; HEADER_SIZE];
+    let mut headers = vec![0; SECTION_HEADER_SIZE as usize];
+    let mut place = |body: &mut Vec<u8>, header: [u32; 10], contents: &[u8]| {
+        for (index, field) in header.into_iter().enumerate() {
+            let field = match index {
+                // filled in now the contents have a place
+                4 => body.len() as u32,
+                5 => contents.len() as u32,
+                _ => field,
+            };
+            headers.write_u32::<BE>(field).unwrap();
+        }
+        body.extend(contents);
+    };
+
+    for ((_, kind, flags, link, info, alignment, entry_size, contents), name) in
+        sections.into_iter().zip(names)
+    {
+        align(&mut body, alignment as usize);
+        let header = [
+            name, kind, flags, 0, 0, 0, link, info, alignment, entry_size,
+        ];
+        place(&mut body, header, contents);
+    }
+    let header = [shstrtab_name, SHT_STRTAB, 0, 0, 0, 0, 0, 0, 1, 0];
+    place(&mut body, header, &shstrtab);
+
+    align(&mut body, 4);
+    let section_headers = body.len() as u32;
+    body.extend(headers);
+
+    let mut header = vec![0x7F, b'E', b'L', b'F', 1, 2, 1];
+    header.resize(16, 0);
+    header.write_u16::<BE>(ET_REL).unwrap();
+    header.write_u16::<BE>(8).unwrap(); // EM_MIPS
+    header.write_u32::<BE>(1).unwrap();
+    header.write_u32::<BE>(0).unwrap();
+    header.write_u32::<BE>(0).unwrap();
+    header.write_u32::<BE>(section_headers).unwrap();
+    header.write_u32::<BE>(E_FLAGS).unwrap();
+    header.write_u16::<BE>(HEADER_SIZE as u16).unwrap();
+    header.write_u16::<BE>(0).unwrap();
+    header.write_u16::<BE>(0).unwrap();
+    header.write_u16::<BE>(SECTION_HEADER_SIZE).unwrap();
+    header.write_u16::<BE>(SHSTRTAB + 1).unwrap();
+    header.write_u16::<BE>(SHSTRTAB).unwrap();
+    body[..HEADER_SIZE].copy_from_slice(&header);
+
+    body
+}
+
+/// A symbol read from an ELF file, with the section it's in
+pub struct Located<'a> {
+    /// Address of the symbol; in a relocatable object, its offset into the section
+    pub vram: u32,
+    /// Bytes the symbol covers, or 0 if the symbol table doesn't say
+    pub size: u32,
+    /// Address of the start of `contents`
+    pub base: u32,
+    /// The section's bytes, up to the end of the last `row_` function at or after the symbol
+    /// if the symbol table names any
+    pub contents: &'a [u8],
+}
+
+/// Bytes `start..start + len` of `file`, if it has them
+fn slice(file: &[u8], start: u32, len: u32) -> Result<&[u8]> {
+    (start as usize)
+        .checked_add(len as usize)
+        .and_then(|end| file.get(start as usize..end))
+        .with_context(|| format!("the ELF file is truncated at 0x{start:X}"))
+}
+
+/// The fields of a section header that locating a symbol needs
+struct Section {
+    kind: u32,
+    addr: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+}
+
+/// Find the symbol `name` in a big-endian 32-bit ELF file, such as a MIPS executable or object
+pub fn locate<'a>(file: &'a [u8], name: &str) -> Result<Located<'a>> {
+    if file.get(..4) != Some(b"\x7FELF") {
+        bail!("not an ELF file");
+    }
+    if file.get(4..6) != Some(&[1, 2]) {
+        bail!("only 32-bit big-endian ELF files are supported");
+    }
+
+    let header = slice(file, 0, HEADER_SIZE as u32)?;
+    let relocatable = BE::read_u16(&header[16..]) == ET_REL;
+    let section_headers = BE::read_u32(&header[32..]);
+    let header_size = u32::from(BE::read_u16(&header[46..]));
+    let num_sections = u32::from(BE::read_u16(&header[48..]));
+
+    let mut sections = vec![];
+    for index in 0..num_sections {
+        let header = slice(file, section_headers + index * header_size, header_size)?;
+        let field = |i: usize| BE::read_u32(&header[i * 4..]);
+        sections.push(Section {
+            kind: field(1),
+            addr: field(3),
+            offset: field(4),
+            size: field(5),
+            link: field(6),
+        });
+    }
+
+    let symtab = sections
+        .iter()
+        .find(|s| s.kind == SHT_SYMTAB)
+        .context("the ELF file has no symbol table")?;
+    let strtab = sections
+        .get(symtab.link as usize)
+        .context("the symbol table's string table is missing")?;
+    let strings = slice(file, strtab.offset, strtab.size)?;
+    let symbols = slice(file, symtab.offset, symtab.size)?;
+
+    // name, value, size and section of each symbol
+    let symbols = symbols
+        .chunks_exact(16)
+        .map(|symbol| {
+            let start = BE::read_u32(symbol) as usize;
+            let name = strings
+                .get(start..)
+                .and_then(|s| s.split(|&b| b == 0).next())
+                .unwrap_or_default();
+            let value = BE::read_u32(&symbol[4..]);
+            let size = BE::read_u32(&symbol[8..]);
+            (name, value, size, BE::read_u16(&symbol[14..]))
+        })
+        .collect::<Vec<_>>();
+
+    let Some(&(_, vram, size, index)) = symbols.iter().find(|(n, ..)| *n == name.as_bytes()) else {
+        bail!("the ELF file has no symbol named {name}");
+    };
+    let Some(section) = sections
+        .get(index as usize)
+        .filter(|_| index != 0 && index < SHN_LORESERVE)
+    else {
+        bail!("{name} isn't defined in a section of the ELF file");
+    };
+    if section.kind == SHT_NOBITS {
+        bail!("{name} is in a section with no contents, such as .bss");
+    }
+
+    let base = if relocatable { 0 } else { section.addr };
+    if vram.wrapping_sub(base) >= section.size {
+        bail!("{name} at 0x{vram:08X} is outside its section");
+    }
+
+    let end = symbols
+        .iter()
+        .filter(|&&(n, value, _, i)| i == index && n.starts_with(b"row_") && value >= vram)
+        .map(|&(_, value, size, _)| value - base + size)
+        .max()
+        .unwrap_or(section.size)
+        .min(section.size);
+
+    Ok(Located {
+        vram,
+        size,
+        base,
+        contents: slice(file, section.offset, end)?,
+    })
+}
@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Input that can't be a font, with the file, glyph or address it was found at
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{} is {width}x{height}, but {problem}", path.display())]
+    BadDimensions {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        problem: String,
+    },
+
+    /// A store to the framebuffer that lands outside the `width` pixels of the row, at `address`,
+    /// in the row function at `function`
+    #[error(
+        "`{instr}` at 0x{address:08X}, in the function at 0x{function:08X}, writes byte {offset} \
+         of the row, outside its {width} pixels"
+    )]
+    StoreOutOfRange {
+        function: u32,
+        address: u32,
+        instr: String,
+        offset: i32,
+        width: usize,
+    },
+
+    #[error(
+        "a table of {num_chars} glyphs needs 0x{needed:X} bytes, but only 0x{available:X} follow \
+         0x{vram:08X}"
+    )]
+    TruncatedTable {
+        vram: u32,
+        num_chars: usize,
+        needed: usize,
+        available: usize,
+    },
+}
@@ -0,0 +1,212 @@
+use crate::Extraction;
+use crate::charmap::Charmap;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Most glyphs a document may index, well past any firmware font, so a stray index can't make
+/// reading it allocate without bound
+pub const MAX_GLYPHS: usize = 0x10000;
+
+/// Text representation of a font, one bitmask string per glyph row
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    /// Bytes between framebuffer rows in the code the font was extracted from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stride: Option<i16>,
+    pub glyphs: Vec<Glyph>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Glyph {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char: Option<char>,
+    pub rows: Vec<String>,
+}
+
+/// The document of a font, naming each glyph's character if there's a charmap
+pub fn to_json(
+    glyphs: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+    stride: Option<i16>,
+    charmap: Option<&Charmap>,
+) -> String {
+    let document = Document {
+        glyph_width: glyph_width as u32,
+        glyph_height: glyph_height as u32,
+        stride,
+        glyphs: glyphs
+            .chunks_exact(glyph_width * glyph_height)
+            .enumerate()
+            .map(|(index, glyph)| Glyph {
+                index,
+                char: charmap.and_then(|c| c.char(index)),
+                rows: glyph
+                    .chunks_exact(glyph_width)
+                    .map(|row| {
+                        row.iter()
+                            .map(|&p| if p != 0 { '1' } else { '0' })
+                            .collect()
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    // serde_json can't fail on these types
+    serde_json::to_string_pretty(&document).unwrap() + "\n"
+}
+
+/// The glyphs of a document, and the stride it was extracted with
+pub fn from_json(
+    text: &str,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Result<(Vec<u8>, Option<i16>)> {
+    let document: Document = serde_json::from_str(text)?;
+
+    if (document.glyph_width, document.glyph_height) != (glyph_width as u32, glyph_height as u32) {
+        bail!(
+            "expected {glyph_width}x{glyph_height} glyphs, not {}x{}",
+            document.glyph_width,
+            document.glyph_height
+        );
+    }
+    let size = glyph_width * glyph_height;
+
+    if let Some(glyph) = document.glyphs.iter().find(|g| g.index >= MAX_GLYPHS) {
+        bail!(
+            "glyph {} is past the last glyph a font can have, {}",
+            glyph.index,
+            MAX_GLYPHS - 1
+        );
+    }
+
+    let count = document
+        .glyphs
+        .iter()
+        .map(|g| g.index + 1)
+        .max()
+        .unwrap_or(0);
+    let mut rv = vec![0; count * size];
+    let mut seen = vec![false; count];
+
+    for glyph in &document.glyphs {
+        if std::mem::replace(&mut seen[glyph.index], true) {
+            bail!("glyph {} is defined twice", glyph.index);
+        }
+
+        if glyph.rows.len() != glyph_height {
+            bail!(
+                "glyph {} has {} rows instead of {glyph_height}",
+                glyph.index,
+                glyph.rows.len()
+            );
+        }
+
+        let pixels = &mut rv[glyph.index * size..(glyph.index + 1) * size];
+        for (y, row) in glyph.rows.iter().enumerate() {
+            if row.len() != glyph_width {
+                bail!(
+                    "glyph {} row {y} is {:?}, not {glyph_width} pixels",
+                    glyph.index,
+                    row
+                );
+            }
+
+            for (x, c) in row.chars().enumerate() {
+                pixels[y * glyph_width + x] = match c {
+                    '0' => 0,
+                    '1' => 0xFF,
+                    _ => bail!("glyph {} row {y} has {c:?}, not 0 or 1", glyph.index),
+                };
+            }
+        }
+    }
+
+    Ok((rv, document.stride))
+}
+
+/// Where an extracted font's table and row functions are, for configuring and diffing the
+/// binary it came from
+#[derive(Serialize)]
+pub struct Metadata {
+    pub vram: u32,
+    /// Address of the code after the table
+    pub code: u32,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    pub glyphs: Vec<GlyphEntries>,
+    pub functions: Vec<Function>,
+}
+
+/// The functions a glyph's table blocks point at
+#[derive(Serialize)]
+pub struct GlyphEntries {
+    pub index: usize,
+    pub single: Vec<u32>,
+    pub double: Vec<u32>,
+}
+
+#[derive(Serialize)]
+pub struct Function {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    /// Whether it's in the extra lines after the functions the glyphs use
+    pub extra: bool,
+    /// Glyphs whose table blocks point at it
+    pub glyphs: Vec<usize>,
+    /// Whether more than one glyph uses it
+    pub shared: bool,
+}
+
+/// The metadata of an extraction
+pub fn metadata_json(extraction: &Extraction) -> String {
+    let block = extraction.glyph_height + usize::from(extraction.row_end);
+    let glyphs = extraction
+        .addresses
+        .chunks_exact(block * 2)
+        .enumerate()
+        .map(|(index, entries)| GlyphEntries {
+            index: extraction.first + index,
+            single: entries[..block].to_vec(),
+            double: entries[block..].to_vec(),
+        })
+        .collect::<Vec<_>>();
+
+    let functions = extraction
+        .functions
+        .iter()
+        .map(|f| {
+            let glyphs = glyphs
+                .iter()
+                .filter(|g| g.single.contains(&f.address) || g.double.contains(&f.address))
+                .map(|g| g.index)
+                .collect::<Vec<_>>();
+            Function {
+                name: f.name.clone(),
+                address: f.address,
+                size: f.size,
+                extra: f.extra,
+                shared: glyphs.len() > 1,
+                glyphs,
+            }
+        })
+        .collect();
+
+    let metadata = Metadata {
+        vram: extraction.vram,
+        code: extraction.code,
+        glyph_width: extraction.glyph_width as u32,
+        glyph_height: extraction.glyph_height as u32,
+        glyphs,
+        functions,
+    };
+
+    // serde_json can't fail on these types
+    serde_json::to_string_pretty(&metadata).unwrap() + "\n"
+}
@@ -0,0 +1,3500 @@
+//! Build N64 font tables of generated MIPS row functions, and extract fonts back out of them.
+//!
+//! Glyph data is 8 bytes per row, one per pixel from left to right, nonzero meaning set; a font
+//! is its glyphs' rows one after another.
+
+use anyhow::{Context, Result, bail};
+use byteorder::{BE, ByteOrder, ReadBytesExt};
+use charmap::Charmap;
+use clap::ValueEnum;
+use clap_num::maybe_hex;
+use error::Error;
+use mips::{Instr, Reg};
+use quirks::Quirk;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use syntax::Syntax;
+
+pub mod asm;
+pub mod bdf;
+pub mod charmap;
+pub mod elf;
+pub mod error;
+pub mod json;
+pub mod manifest;
+pub mod mips;
+pub mod presets;
+pub mod psf;
+pub mod quirks;
+pub mod raw;
+pub mod report;
+pub mod rom;
+pub mod roundtrip;
+pub mod scan;
+pub mod scratch;
+pub mod syntax;
+pub mod timings;
+pub mod txt;
+
+/// Default framebuffer width in pixels
+pub const SCREEN_WIDTH: i16 = 640;
+
+/// How the framebuffer stores a pixel
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PixelFormat {
+    /// 16-bit color: pixels are stored with `sh`, pairs with `sw`
+    #[default]
+    Rgba16,
+    /// 32-bit color: pixels are stored with `sw`, pairs with `sd`
+    Rgba32,
+}
+
+impl PixelFormat {
+    /// Bytes per pixel
+    pub fn size(self) -> usize {
+        match self {
+            PixelFormat::Rgba16 => 2,
+            PixelFormat::Rgba32 => 4,
+        }
+    }
+
+    /// Bytes between the rows of a framebuffer `width` pixels wide, if that fits an immediate
+    pub fn stride(self, width: i16) -> Option<i16> {
+        width.checked_mul(self.size() as i16)
+    }
+
+    /// Bytes between the rows of a [`SCREEN_WIDTH`] framebuffer
+    pub fn default_stride(self) -> i16 {
+        SCREEN_WIDTH * self.size() as i16
+    }
+
+    /// The C type of a pixel
+    pub fn c_type(self) -> &'static str {
+        match self {
+            PixelFormat::Rgba16 => "u16",
+            PixelFormat::Rgba32 => "u32",
+        }
+    }
+
+    /// Store of one pixel at byte `offset` of the row
+    fn single(self, offset: i16, registers: Registers) -> Instr {
+        let Registers {
+            framebuffer, color, ..
+        } = registers;
+        match self {
+            PixelFormat::Rgba16 => Instr::Sh(color, offset, framebuffer),
+            PixelFormat::Rgba32 => Instr::Sw(color, offset, framebuffer),
+        }
+    }
+
+    /// Store of two pixels starting at byte `offset` of the row
+    fn pair(self, offset: i16, registers: Registers) -> Instr {
+        let Registers {
+            framebuffer, color, ..
+        } = registers;
+        match self {
+            PixelFormat::Rgba16 => Instr::Sw(color, offset, framebuffer),
+            PixelFormat::Rgba32 => Instr::Sd(color, offset, framebuffer),
+        }
+    }
+
+    /// The byte offset and pixel count of a store of the color to the row
+    fn store(self, instr: Option<Instr>, registers: Registers) -> Option<(i16, usize)> {
+        let (count, rt, offset, base) = match (self, instr?) {
+            (PixelFormat::Rgba16, Instr::Sh(rt, offset, base))
+            | (PixelFormat::Rgba32, Instr::Sw(rt, offset, base)) => (1, rt, offset, base),
+            (PixelFormat::Rgba16, Instr::Sw(rt, offset, base))
+            | (PixelFormat::Rgba32, Instr::Sd(rt, offset, base)) => (2, rt, offset, base),
+            _ => return None,
+        };
+        ((rt, base) == (registers.color, registers.framebuffer)).then_some((offset, count))
+    }
+}
+
+/// Grays that extracted pixels come out as, by the kind of store that drew them
+#[derive(Clone, Copy)]
+pub struct Shades {
+    /// A pixel stored on its own
+    pub single: u8,
+    /// A pixel stored as half of a pair
+    pub pair: u8,
+}
+
+impl Default for Shades {
+    fn default() -> Self {
+        Self {
+            single: 0xFF,
+            pair: 0x7F,
+        }
+    }
+}
+
+impl Shades {
+    /// Repaint pixels extracted with the default shades in these ones
+    pub fn apply(self, pixels: &[u8]) -> Vec<u8> {
+        let default = Shades::default();
+        pixels
+            .iter()
+            .map(|&p| match p {
+                _ if p == default.single => self.single,
+                _ if p == default.pair => self.pair,
+                _ => p,
+            })
+            .collect()
+    }
+}
+
+/// Start of a generated `.s` file, before the tables
+pub const PROLOGUE: &str = include_str!("prologue.s");
+/// End of a generated `.s` file, after the functions
+pub const EPILOGUE: &str = include_str!("epilogue.s");
+
+/// Source of the function ending every table block
+pub const ROW_END: &str = include_str!("row_end.s");
+
+/// The assembly around the generated code, for projects with other macro conventions
+#[derive(Clone, Copy)]
+pub struct Framing<'a> {
+    pub prologue: &'a str,
+    pub epilogue: &'a str,
+    /// Source of `row_end`; the machine code of binary output always pops the color and next
+    /// registers
+    pub row_end: &'a str,
+}
+
+impl Default for Framing<'_> {
+    fn default() -> Self {
+        Self {
+            prologue: PROLOGUE,
+            epilogue: EPILOGUE,
+            row_end: ROW_END,
+        }
+    }
+}
+
+/// Registers the row functions keep their state in, for firmware whose call sites use other
+/// ones than the original's
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    /// The next word of the table block
+    pub list: Reg,
+    /// The framebuffer row being drawn
+    pub framebuffer: Reg,
+    /// The color every set pixel is stored as
+    pub color: Reg,
+    /// The function each row function jumps to, loaded from the list
+    pub next: Reg,
+    /// The table, for tables of halfword offsets from it instead of addresses
+    pub base: Option<Reg>,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self {
+            list: Reg::A0,
+            framebuffer: Reg::A1,
+            color: Reg::S1,
+            next: Reg::S0,
+            base: None,
+        }
+    }
+}
+
+impl Registers {
+    /// Check the registers are distinct and free for the generated code to use, and with
+    /// `entry` that the C wrappers can set them up from their arguments
+    pub fn check(self, entry: bool) -> Result<()> {
+        let Registers {
+            list,
+            framebuffer,
+            color,
+            next,
+            base,
+        } = self;
+        let all = [list, framebuffer, color, next]
+            .into_iter()
+            .chain(base)
+            .collect::<Vec<_>>();
+
+        for (i, reg) in all.iter().enumerate() {
+            if all[..i].contains(reg) {
+                bail!("${} is given more than one role", reg.name());
+            }
+            if matches!(
+                reg,
+                Reg::Zero | Reg::At | Reg::K0 | Reg::K1 | Reg::Gp | Reg::Sp | Reg::Ra
+            ) {
+                bail!(
+                    "${} is reserved and can't be used by the row functions",
+                    reg.name()
+                );
+            }
+        }
+
+        if entry {
+            // the wrappers only save what row_end restores, and build the color from a2 in a3
+            let arguments = [Reg::A0, Reg::A1, Reg::A2, Reg::A3];
+            for (what, reg, argument) in [
+                ("list", list, Reg::A0),
+                ("framebuffer", framebuffer, Reg::A1),
+            ] {
+                if reg != argument && (arguments.contains(&reg) || reg.is_saved()) {
+                    bail!(
+                        "the C entry points need the {what} in ${} or a temporary register, not ${}",
+                        argument.name(),
+                        reg.name()
+                    );
+                }
+            }
+            if arguments.contains(&color) {
+                bail!(
+                    "the C entry points need the color outside $a0-$a3, not in ${}",
+                    color.name()
+                );
+            }
+            if let Some(base) = base
+                && (arguments.contains(&base) || base.is_saved())
+            {
+                bail!(
+                    "the C entry points need the table base in a temporary register, not ${}",
+                    base.name()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bytes of each table entry: an address, or a halfword offset from `base`
+    pub fn entry_size(self) -> usize {
+        match self.base {
+            Some(_) => size_of::<u16>(),
+            None => size_of::<u32>(),
+        }
+    }
+
+    /// The addresses the entries of a table at `vram` point at
+    pub fn entries(self, table: &[u8], vram: u32) -> Vec<u32> {
+        match self.base {
+            Some(_) => table
+                .chunks_exact(size_of::<u16>())
+                .map(|h| vram.wrapping_add(BE::read_u16(h).into()))
+                .collect(),
+            None => table
+                .chunks_exact(size_of::<u32>())
+                .map(BE::read_u32)
+                .collect(),
+        }
+    }
+}
+
+/// Code drawing one row of `width` pixels, leftmost in the top bit of `row`: the set pixels,
+/// in pairs with `double`, then advancing `stride` bytes to the next framebuffer row and jumping
+/// to the next table entry
+pub fn build_function(
+    row: u32,
+    width: usize,
+    double: bool,
+    format: PixelFormat,
+    stride: i16,
+    quirks: &[Quirk],
+    registers: Registers,
+) -> Vec<Instr> {
+    let Registers {
+        list,
+        framebuffer,
+        next,
+        base,
+        ..
+    } = registers;
+    let width = width as u32;
+    let mut rv = vec![];
+
+    match base {
+        Some(base) => {
+            rv.push(Instr::Lhu(next, 0, list));
+            rv.push(Instr::Addi(list, list, size_of::<u16>() as i16));
+            rv.push(Instr::Addu(next, next, base));
+        }
+        None => {
+            rv.push(Instr::Lw(next, 0, list));
+            rv.push(Instr::Addi(list, list, size_of::<u32>() as i16));
+        }
+    }
+
+    for i in (0..width).step_by(2) {
+        let pair = (row >> (width - i - 2)) & 0b00000011;
+        let offset = (i as usize * format.size()) as i16;
+        let second = ((i + 1) as usize * format.size()) as i16;
+        let start = rv.len();
+        match pair {
+            0b00 => {}
+            0b01 => rv.push(format.single(second, registers)),
+            0b10 => rv.push(format.single(offset, registers)),
+            0b11 if double => rv.push(format.pair(offset, registers)),
+            0b11 => {
+                rv.push(format.single(offset, registers));
+                rv.push(format.single(second, registers));
+            }
+            _ => unreachable!(),
+        }
+
+        if let Some(quirk) = quirks
+            .iter()
+            .find(|q| u32::from(q.row) == row && q.double == double && q.pair == i / 2)
+            && start < rv.len()
+        {
+            rv[start] = quirk.instr;
+        }
+    }
+
+    rv.push(Instr::Jr(next));
+    rv.push(Instr::Addi(framebuffer, framebuffer, stride));
+
+    rv
+}
+
+/// An item of the code section, in emission order
+pub enum Function {
+    Row(String, Vec<Instr>),
+    /// `row_end`, with its machine code
+    End([Instr; 6]),
+    /// A word of padding between or after the functions
+    Data(u32),
+}
+
+impl Function {
+    fn name(&self) -> &str {
+        match self {
+            Function::Row(name, _) => name,
+            Function::End(_) => "row_end",
+            Function::Data(_) => "",
+        }
+    }
+
+    fn body(&self) -> &[Instr] {
+        match self {
+            Function::Row(_, body) => body,
+            Function::End(body) => body,
+            Function::Data(_) => &[],
+        }
+    }
+
+    fn source(&self, row_end: &str, syntax: Syntax) -> String {
+        match self {
+            Function::Row(name, body) => {
+                format!(
+                    "{}{}{}\n",
+                    syntax.leaf(name),
+                    syntax.body(body),
+                    syntax.end(name)
+                )
+            }
+            Function::End(_) => row_end.to_string(),
+            Function::Data(word) => syntax.word(&format!("0x{word:08X}")) + "\n",
+        }
+    }
+}
+
+/// Name of the function drawing `row` of a `width`-pixel glyph from the single or double block
+pub fn row_name(kind: &str, row: u32, width: usize) -> String {
+    format!("row_{kind}_{row:0width$b}")
+}
+
+/// The rows of each glyph; glyphs are `glyph_height` rows of `glyph_width` pixel bytes,
+/// nonzero is set
+pub fn glyph_rows(data: &[u8], glyph_width: usize, glyph_height: usize) -> Vec<Vec<u32>> {
+    data.par_chunks_exact(glyph_width * glyph_height)
+        .map(|ch| line_rows(ch, glyph_width))
+        .collect()
+}
+
+fn base_rows(glyph_width: usize) -> Vec<u32> {
+    let mut rows = vec![];
+
+    // the firmware's fonts are all 8 pixels wide; wider ones only get the rows they use
+    if glyph_width != 8 {
+        return rows;
+    }
+
+    for i in 0..(1 << 7) {
+        rows.push(((i << 3) & 0b11111000) | ((i >> 4) & 0b00000110));
+    }
+
+    rows
+}
+
+fn line_rows(data: &[u8], glyph_width: usize) -> Vec<u32> {
+    let mut rows = vec![];
+
+    for row in data.chunks_exact(glyph_width) {
+        let mut b = 0;
+
+        for i in row {
+            b = (b << 1) | (*i != 0) as u32;
+        }
+
+        rows.push(b);
+    }
+
+    rows
+}
+
+/// Graphviz graph of which glyphs and extra lines use each row
+pub fn usage_graph(data: &[u8], extra: &[u8], glyph_width: usize, glyph_height: usize) -> String {
+    let w = glyph_width;
+    let char_rows = glyph_rows(data, glyph_width, glyph_height);
+    let rows = base_rows(glyph_width);
+    let extra_rows = line_rows(extra, glyph_width);
+
+    let mut uses = HashMap::new();
+    for ch in &char_rows {
+        for i in ch {
+            *uses.entry(*i).or_insert(0) += 1;
+        }
+    }
+
+    let mut rv = String::from("digraph gsfont {\n    rankdir=LR;\n    node [shape=box];\n\n");
+
+    let mut nodes = uses.keys().chain(&extra_rows).copied().collect::<Vec<_>>();
+    nodes.sort();
+    nodes.dedup();
+
+    for i in nodes {
+        let count = uses.get(&i).copied().unwrap_or(0);
+        let style = if rows.contains(&i) {
+            ""
+        } else if extra_rows.contains(&i) {
+            ", style=filled, fillcolor=lightblue"
+        } else {
+            ", style=filled, fillcolor=lightyellow"
+        };
+        let style = if count == 0 { ", style=dashed" } else { style };
+        rv += &format!("    row_{i:0w$b} [label=\"{i:0w$b}\\n{count} uses\"{style}];\n");
+    }
+    rv += "\n";
+
+    for (index, ch) in char_rows.iter().enumerate() {
+        rv += &format!("    glyph_{index:02X} [shape=ellipse, label=\"0x{index:02X}\"];\n");
+
+        let mut targets = ch.to_vec();
+        targets.sort();
+        targets.dedup();
+        for i in targets {
+            let count = ch.iter().filter(|&&r| r == i).count();
+            if count > 1 {
+                rv += &format!("    glyph_{index:02X} -> row_{i:0w$b} [label=\"{count}\"];\n");
+            } else {
+                rv += &format!("    glyph_{index:02X} -> row_{i:0w$b};\n");
+            }
+        }
+    }
+
+    rv += "}\n";
+    rv
+}
+
+/// Extra rows that are already in the base set or earlier in the extra lines
+pub fn redundant_extras(extra: &[u8], glyph_width: usize) -> Vec<String> {
+    let w = glyph_width;
+    let rows = base_rows(glyph_width);
+    let extra_rows = line_rows(extra, glyph_width);
+    let mut rv = vec![];
+
+    for (index, row) in extra_rows.iter().enumerate() {
+        if rows.contains(row) {
+            rv.push(format!(
+                "extra row {index} ({row:0w$b}) is already in the base set"
+            ));
+        } else if let Some(first) = extra_rows[..index].iter().position(|r| r == row) {
+            rv.push(format!(
+                "extra row {index} ({row:0w$b}) duplicates extra row {first}"
+            ));
+        }
+    }
+
+    rv
+}
+
+/// One function name per line, optionally followed by other fields; `#` starts a comment
+pub fn parse_order(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+fn apply_order(functions: Vec<Function>, order: &[String]) -> Result<Vec<Function>> {
+    let mut remaining = functions.into_iter().map(Some).collect::<Vec<_>>();
+    let mut rv = vec![];
+
+    for name in order {
+        let Some(function) = remaining
+            .iter_mut()
+            .find(|f| f.as_ref().is_some_and(|f| f.name() == name))
+        else {
+            bail!("order file names {name}, which is not generated or is listed twice");
+        };
+        rv.extend(function.take());
+    }
+
+    // anything the order file doesn't mention keeps its default position at the end
+    rv.extend(remaining.into_iter().flatten());
+
+    Ok(rv)
+}
+
+/// Order that rows the glyphs use beyond the base set and extra lines get their functions in
+#[derive(Clone, Copy, Default)]
+pub enum ExtraOrder<'a> {
+    /// The order the glyphs first use them in
+    #[default]
+    FirstSeen,
+    /// By their pixels, as in the function names
+    Sorted,
+    /// These rows first, in this order, then any others the glyphs use in first-seen order;
+    /// listed rows the glyphs don't use are skipped
+    Listed(&'a [u32]),
+}
+
+/// One row per line, as the pixels of its function name such as `00111100`; `#` starts a
+/// comment
+pub fn parse_extra_order(text: &str, glyph_width: usize) -> Result<Vec<u32>> {
+    let mut rv = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let Some(row) = line
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .next()
+        else {
+            continue;
+        };
+        let row = row
+            .trim_start_matches("row_single_")
+            .trim_start_matches("row_double_");
+        if row.len() != glyph_width {
+            bail!(
+                "line {}: row {row:?} is not {glyph_width} pixels",
+                number + 1
+            );
+        }
+        let Ok(row) = u32::from_str_radix(row, 2) else {
+            bail!("line {}: row {row:?} is not 0s and 1s", number + 1);
+        };
+        rv.push(row);
+    }
+
+    Ok(rv)
+}
+
+/// One function name or `.word <value>` per line; `#` starts a comment
+pub fn parse_extra_layout(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+/// Move the functions named by the extra layout to the end in its order, generating any that
+/// aren't already, with its padding words in between
+fn apply_extra_layout(
+    mut functions: Vec<Function>,
+    extra_layout: &[String],
+    pixel_format: PixelFormat,
+    stride: i16,
+    quirks: &[Quirk],
+    registers: Registers,
+) -> Result<Vec<Function>> {
+    let mut tail = vec![];
+
+    for entry in extra_layout {
+        if let Some(word) = entry.strip_prefix(".word ") {
+            let Ok(word) = maybe_hex::<u32>(word) else {
+                bail!("extra layout word {word:?} is not a number");
+            };
+            tail.push(Function::Data(word));
+            continue;
+        }
+
+        if let Some(index) = functions.iter().position(|f| f.name() == entry) {
+            tail.push(functions.remove(index));
+            continue;
+        }
+
+        // the row's width is however many bits its name has
+        let row = |prefix| {
+            let bits = entry.strip_prefix(prefix)?;
+            let width = bits.len();
+            let valid = width.is_multiple_of(2) && width <= u32::BITS as usize;
+            Some((u32::from_str_radix(bits, 2).ok().filter(|_| valid)?, width))
+        };
+        match (row("row_single_"), row("row_double_")) {
+            (Some((i, width)), _) => tail.push(Function::Row(
+                entry.clone(),
+                build_function(i, width, false, pixel_format, stride, quirks, registers),
+            )),
+            (_, Some((i, width))) => tail.push(Function::Row(
+                entry.clone(),
+                build_function(i, width, true, pixel_format, stride, quirks, registers),
+            )),
+            _ => {
+                bail!("extra layout names {entry}, which is not a row function or is listed twice")
+            }
+        }
+    }
+
+    functions.extend(tail);
+
+    Ok(functions)
+}
+
+/// Per-glyph comments giving the index and, if the charmap has one, the character
+pub fn glyph_comments(charmap: &Charmap, num_chars: usize) -> Vec<String> {
+    (0..num_chars)
+        .map(|index| match charmap.char(index) {
+            Some(c) => format!("0x{index:02X} {c:?}"),
+            None => format!("0x{index:02X}"),
+        })
+        .collect()
+}
+
+/// Per-glyph `(single, double)` table labels from the charmap's names
+pub fn glyph_labels(
+    charmap: &Charmap,
+    num_chars: usize,
+    first_label: &str,
+    second_label: &str,
+) -> Result<Vec<(String, String)>> {
+    for label in [first_label, second_label] {
+        if !charmap::is_identifier(label) {
+            bail!("{label:?} is not a valid label");
+        }
+    }
+
+    let mut rv = vec![];
+    let mut seen = HashMap::new();
+    seen.insert(first_label.to_string(), "the first table".to_string());
+    seen.insert(second_label.to_string(), "the second table".to_string());
+
+    for index in 0..num_chars {
+        let name = match charmap.char(index) {
+            Some(c) => charmap::sanitize(c),
+            None => format!("glyph_{index:02X}"),
+        };
+
+        let labels = (
+            format!("{first_label}_{name}"),
+            format!("{second_label}_{name}"),
+        );
+
+        for label in [&labels.0, &labels.1] {
+            if let Some(other) = seen.insert(label.clone(), format!("glyph 0x{index:02X}")) {
+                bail!("label {label} for glyph 0x{index:02X} collides with {other}");
+            }
+        }
+
+        rv.push(labels);
+    }
+
+    Ok(rv)
+}
+
+/// How [`build`] lays out and names the tables and functions
+pub struct BuildOptions<'a> {
+    pub first_label: &'a str,
+    pub second_label: &'a str,
+    pub extra: &'a [u8],
+    pub quirks: &'a [Quirk],
+    pub incbin: Option<&'a str>,
+    pub order: Option<&'a [String]>,
+    /// Order of the functions for rows only the glyphs use
+    pub extra_order: ExtraOrder<'a>,
+    pub glyph_labels: &'a [(String, String)],
+    /// Comments naming each glyph's index and character, written above its table blocks
+    pub glyph_comments: &'a [String],
+    /// Pixels per glyph row, which each row function draws all of
+    pub glyph_width: usize,
+    /// Rows per glyph; each table block is this many row functions and `row_end`
+    pub glyph_height: usize,
+    /// How the framebuffer stores the pixels the row functions draw
+    pub pixel_format: PixelFormat,
+    /// Bytes between framebuffer rows, which every row function advances by
+    pub stride: i16,
+    /// Emit a C-callable `{first_label}_draw` entry point
+    pub entry: bool,
+    /// Names of C-callable wrappers drawing each glyph from its single and double blocks
+    pub glyph_entries: &'a [(String, String)],
+    /// Pixel values the glyph wrappers draw the single and double blocks in, instead of taking a
+    /// color
+    pub glyph_colors: Option<(u32, u32)>,
+    /// Other fonts whose tables follow this one's and use the same row functions
+    pub shared: &'a [SharedFont],
+    /// Functions and padding words ending the code, as written by `extract --extra-layout`
+    pub extra_layout: Option<&'a [String]>,
+    pub framing: Framing<'a>,
+    pub registers: Registers,
+    pub syntax: Syntax,
+}
+
+/// Another font built against the same row functions, with its own tables
+pub struct SharedFont {
+    pub glyphs: Vec<u8>,
+    pub first_label: String,
+    pub second_label: String,
+}
+
+/// The row functions in emission order and the table entries naming them
+pub fn layout(data: &[u8], options: &BuildOptions) -> Result<(Vec<Function>, Vec<String>)> {
+    let BuildOptions {
+        extra,
+        quirks,
+        order,
+        extra_order,
+        glyph_labels,
+        glyph_width,
+        glyph_height,
+        pixel_format,
+        stride,
+        shared,
+        extra_layout,
+        registers,
+        ..
+    } = *options;
+
+    let char_rows = glyph_rows(data, glyph_width, glyph_height);
+    let shared_rows = shared
+        .iter()
+        .flat_map(|font| glyph_rows(&font.glyphs, glyph_width, glyph_height))
+        .collect::<Vec<_>>();
+    let rows = base_rows(glyph_width);
+    let mut extra_rows = line_rows(extra, glyph_width);
+
+    let mut seen = rows
+        .iter()
+        .chain(&extra_rows)
+        .copied()
+        .collect::<HashSet<_>>();
+    let mut discovered = vec![];
+    for ch in char_rows.iter().chain(&shared_rows) {
+        for &i in ch {
+            if seen.insert(i) {
+                discovered.push(i);
+            }
+        }
+    }
+    match extra_order {
+        ExtraOrder::FirstSeen => {}
+        ExtraOrder::Sorted => discovered.sort_unstable(),
+        ExtraOrder::Listed(listed) => {
+            let mut ordered = vec![];
+            for row in listed {
+                if let Some(i) = discovered.iter().position(|r| r == row) {
+                    ordered.push(discovered.remove(i));
+                }
+            }
+            ordered.append(&mut discovered);
+            discovered = ordered;
+        }
+    }
+    extra_rows.extend(discovered);
+
+    // built in parallel, but collected in order
+    let row_functions = |rows: &[u32], double: bool| {
+        let kind = if double { "double" } else { "single" };
+        rows.par_iter()
+            .map(|&i| {
+                Function::Row(
+                    row_name(kind, i, glyph_width),
+                    build_function(
+                        i,
+                        glyph_width,
+                        double,
+                        pixel_format,
+                        stride,
+                        quirks,
+                        registers,
+                    ),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut functions = row_functions(&rows, false);
+    functions.push(Function::End(mips::row_end(
+        registers.color,
+        registers.next,
+    )));
+    functions.extend(row_functions(&rows, true));
+    functions.extend(row_functions(&extra_rows, true));
+    functions.extend(row_functions(&extra_rows, false));
+
+    if let Some(order) = order {
+        functions = apply_order(functions, order)?;
+    }
+
+    if let Some(extra_layout) = extra_layout {
+        functions = apply_extra_layout(
+            functions,
+            extra_layout,
+            pixel_format,
+            stride,
+            quirks,
+            registers,
+        )?;
+    }
+
+    for (single, double) in glyph_labels {
+        if let Some(f) = functions
+            .iter()
+            .find(|f| f.name() == single || f.name() == double)
+        {
+            bail!("glyph label collides with row function {}", f.name());
+        }
+    }
+
+    Ok((functions, glyph_table(&char_rows, glyph_width)))
+}
+
+/// The single and then double block of row functions of each glyph
+fn glyph_table(char_rows: &[Vec<u32>], glyph_width: usize) -> Vec<String> {
+    char_rows
+        .par_iter()
+        .flat_map_iter(|row| {
+            let block = |kind| {
+                row.iter()
+                    .map(move |&i| row_name(kind, i, glyph_width))
+                    .chain(["row_end".to_string()])
+            };
+            block("single").chain(block("double"))
+        })
+        .collect()
+}
+
+/// Machine code for the functions, and the offset of each one by name
+fn encode(functions: &[Function]) -> (Vec<u8>, HashMap<&str, usize>) {
+    let mut bin = vec![];
+    let mut offsets = HashMap::new();
+
+    for function in functions {
+        offsets.insert(function.name(), bin.len());
+        if let Function::Data(word) = function {
+            bin.extend(word.to_be_bytes());
+        }
+        for instr in function.body() {
+            bin.extend(instr.encode().to_be_bytes());
+        }
+    }
+
+    (bin, offsets)
+}
+
+/// The table followed by the functions, as they would be linked at `vram`, with entries of
+/// addresses or of offsets from the table as `registers` reads them
+fn assemble(
+    functions: &[Function],
+    table: &[String],
+    vram: u32,
+    registers: Registers,
+) -> Result<Vec<u8>> {
+    let (code, offsets) = encode(functions);
+    let table_size = table.len() * registers.entry_size();
+
+    let mut rv = vec![];
+    for entry in table {
+        let offset = table_size + offsets[entry.as_str()];
+        match registers.base {
+            Some(_) => match u16::try_from(offset) {
+                Ok(offset) => rv.extend(offset.to_be_bytes()),
+                Err(_) => bail!(
+                    "{entry} is 0x{offset:X} bytes from the table, too far for a halfword offset"
+                ),
+            },
+            None => rv.extend((vram + offset as u32).to_be_bytes()),
+        }
+    }
+    rv.extend(code);
+
+    Ok(rv)
+}
+
+/// A table's entries with its first and second labels, glyph labels and glyph comments
+type LabelledTable<'a> = (
+    Vec<String>,
+    &'a str,
+    &'a str,
+    &'a [(String, String)],
+    &'a [String],
+);
+
+/// The row functions, and this font's tables followed by those of the shared fonts
+fn tables<'a>(
+    data: &[u8],
+    options: &BuildOptions<'a>,
+) -> Result<(Vec<Function>, Vec<LabelledTable<'a>>)> {
+    let BuildOptions {
+        first_label,
+        second_label,
+        glyph_labels,
+        glyph_comments,
+        glyph_width,
+        glyph_height,
+        shared,
+        ..
+    } = *options;
+
+    let (functions, table) = layout(data, options)?;
+
+    let mut tables = vec![(
+        table,
+        first_label,
+        second_label,
+        glyph_labels,
+        glyph_comments,
+    )];
+    for font in shared {
+        tables.push((
+            glyph_table(
+                &glyph_rows(&font.glyphs, glyph_width, glyph_height),
+                glyph_width,
+            ),
+            font.first_label.as_str(),
+            font.second_label.as_str(),
+            &[],
+            &[],
+        ));
+    }
+
+    Ok((functions, tables))
+}
+
+/// The tables, including those of shared fonts, and then the row functions, assembled as they
+/// would be linked at `vram`
+pub fn build_binary(data: &[u8], options: &BuildOptions, vram: u32) -> Result<Vec<u8>> {
+    let (functions, tables) = tables(data, options)?;
+    let table = tables.into_iter().flat_map(|(t, ..)| t).collect::<Vec<_>>();
+    timings::lap("row packing");
+
+    let rv = assemble(&functions, &table, vram, options.registers)?;
+    timings::lap("codegen");
+
+    Ok(rv)
+}
+
+/// The same tables and row functions as [`build_binary`], as a relocatable object exporting the
+/// labels the assembly would
+pub fn build_object(data: &[u8], options: &BuildOptions) -> Result<Vec<u8>> {
+    let glyph_height = options.glyph_height;
+    if options.registers.base.is_some() {
+        bail!("objects hold tables of addresses; assemble the source for a table of offsets");
+    }
+
+    let (functions, tables) = tables(data, options)?;
+    timings::lap("row packing");
+
+    let block_size = (glyph_height + 1) * size_of::<u32>();
+    let table_size = tables.iter().map(|(t, ..)| t.len()).sum::<usize>() * size_of::<u32>();
+    let (code, offsets) = encode(&functions);
+
+    let mut text = vec![];
+    let mut symbols = vec![];
+    let label = |name: &str, offset: usize, size: usize, function: bool| elf::Symbol {
+        name: name.to_string(),
+        offset: offset as u32,
+        size: size as u32,
+        function,
+    };
+    for (table, first_label, second_label, glyph_labels, _) in &tables {
+        // the first and second labels run to the end of their table
+        let size = table.len() * size_of::<u32>();
+        symbols.push(label(first_label, text.len(), size, false));
+        symbols.push(label(
+            second_label,
+            text.len() + block_size,
+            size - block_size,
+            false,
+        ));
+        for (index, (single, double)) in glyph_labels.iter().enumerate() {
+            let offset = text.len() + index * 2 * block_size;
+            symbols.push(label(single, offset, block_size, false));
+            symbols.push(label(double, offset + block_size, block_size, false));
+        }
+
+        for entry in table {
+            text.extend(((table_size + offsets[entry.as_str()]) as u32).to_be_bytes());
+        }
+    }
+    let relocations = (0..table_size as u32)
+        .step_by(size_of::<u32>())
+        .collect::<Vec<_>>();
+
+    for function in &functions {
+        if let Function::Data(_) = function {
+            continue;
+        }
+        let offset = table_size + offsets[function.name()];
+        let size = function.body().len() * size_of::<u32>();
+        symbols.push(label(function.name(), offset, size, true));
+    }
+    text.extend(code);
+
+    let rv = elf::relocatable(&text, &relocations, &symbols);
+    timings::lap("codegen");
+
+    Ok(rv)
+}
+
+/// The table and row functions of a generated `.s` file, assembled as they would be linked at
+/// `vram`; entry points after the row functions are left out
+pub fn assemble_source(source: &asm::Source, vram: u32) -> Result<Vec<u8>> {
+    let mut functions = vec![];
+    let mut padding = source.padding.iter().peekable();
+    for (index, (name, body)) in source.functions.iter().enumerate() {
+        while let Some((_, word)) = padding.next_if(|(before, _)| *before == index) {
+            functions.push(Function::Data(*word));
+        }
+        functions.push(match name.as_str() {
+            "row_end" => Function::End(mips::ROW_END),
+            _ => Function::Row(name.clone(), body.clone()),
+        });
+    }
+    functions.extend(padding.map(|(_, word)| Function::Data(*word)));
+
+    let names = functions.iter().map(Function::name).collect::<HashSet<_>>();
+    if let Some(entry) = source.table.iter().find(|e| !names.contains(e.as_str())) {
+        bail!("table entry {entry} isn't a row function of the file");
+    }
+
+    assemble(&functions, &source.table, vram, Registers::default())
+}
+
+/// Everything a build produces, in sections that can be placed individually
+pub struct BuildArtifacts {
+    /// Everything before the tables
+    pub prologue: String,
+    /// The pointer tables, including those of shared fonts
+    pub tables: String,
+    /// The row functions, or the `.incbin` standing in for them
+    pub functions: String,
+    /// The C-callable entry points
+    pub entries: String,
+    /// Machine code of the row functions, with `--incbin`
+    pub bin: Vec<u8>,
+    /// Every label exported by the tables and entry points
+    pub labels: Vec<String>,
+    /// Bytes of pointer tables
+    pub table_size: usize,
+    /// Bytes of row function code
+    pub code_size: usize,
+    /// Names of the quirks that changed a generated function
+    pub quirks: Vec<String>,
+    /// Everything after the entry points
+    pub epilogue: String,
+}
+
+impl BuildArtifacts {
+    /// The sections as one assembly file
+    pub fn source(&self) -> String {
+        self.prologue.clone() + &self.tables + &self.functions + &self.entries + &self.epilogue
+    }
+}
+
+/// Generate the tables and row functions for a font
+pub fn build(data: &[u8], options: &BuildOptions) -> Result<BuildArtifacts> {
+    let BuildOptions {
+        first_label,
+        quirks,
+        incbin,
+        glyph_height,
+        entry,
+        glyph_entries,
+        framing,
+        syntax,
+        ..
+    } = *options;
+
+    let (functions, mut tables) = tables(data, options)?;
+    timings::lap("row packing");
+
+    let (code, offsets) = encode(&functions);
+    let mut rv = BuildArtifacts {
+        prologue: framing.prologue.to_string(),
+        tables: String::new(),
+        functions: String::new(),
+        entries: String::new(),
+        bin: vec![],
+        labels: vec![],
+        table_size: tables.iter().map(|(t, ..)| t.len()).sum::<usize>()
+            * options.registers.entry_size(),
+        code_size: code.len(),
+        quirks: quirks
+            .iter()
+            .filter(|q| q.has_store(options.glyph_width))
+            .filter(|q| functions.iter().any(|f| f.name() == q.function()))
+            .map(|q| q.name.clone())
+            .collect(),
+        epilogue: framing.epilogue.to_string(),
+    };
+
+    if let Some(path) = incbin {
+        let rows_label = format!("{first_label}_rows");
+
+        for (table, ..) in &mut tables {
+            for entry in table {
+                *entry = format!("{rows_label} + 0x{:X}", offsets[entry.as_str()]);
+            }
+        }
+        rv.bin = code;
+
+        rv.functions = syntax.export(&rows_label) + &syntax.incbin(path) + "\n";
+        rv.labels.push(rows_label);
+    } else {
+        rv.functions = functions
+            .par_iter()
+            .map(|function| function.source(framing.row_end, syntax))
+            .collect::<Vec<_>>()
+            .concat();
+    }
+
+    for (table, first_label, second_label, glyph_labels, glyph_comments) in &tables {
+        rv.tables += &table_source(
+            table,
+            (first_label, second_label),
+            glyph_labels,
+            glyph_comments,
+            glyph_height,
+            options.registers,
+            syntax,
+        );
+        rv.labels.push(first_label.to_string());
+        rv.labels.push(second_label.to_string());
+        for (single, double) in glyph_labels.iter() {
+            rv.labels.push(single.clone());
+            rv.labels.push(double.clone());
+        }
+    }
+
+    if entry || !glyph_entries.is_empty() {
+        rv.entries = entry_source(options);
+        rv.labels.push(format!("{first_label}_draw"));
+        for (single, double) in glyph_entries {
+            rv.labels.push(format!("{single}_draw"));
+            rv.labels.push(format!("{double}_draw"));
+        }
+    }
+    timings::lap("codegen");
+
+    Ok(rv)
+}
+
+/// One function per glyph whose rows run straight into each other, jumping into an earlier
+/// glyph's code once the remaining rows are the same as that glyph's
+pub fn chained_build(data: &[u8], options: &BuildOptions) -> Result<BuildArtifacts> {
+    let BuildOptions {
+        first_label,
+        second_label,
+        quirks,
+        glyph_width,
+        glyph_height,
+        pixel_format,
+        stride,
+        framing,
+        registers,
+        syntax,
+        ..
+    } = *options;
+
+    if registers.base.is_some() {
+        bail!("chained glyphs jump between their rows instead of reading a table of offsets");
+    }
+
+    let char_rows = glyph_rows(data, glyph_width, glyph_height);
+    timings::lap("row packing");
+    let advance = Instr::Addi(registers.framebuffer, registers.framebuffer, stride);
+
+    let mut tables = String::new();
+    let mut functions = vec![];
+    let mut targets = HashSet::new();
+    let mut instructions = 0;
+    let mut applied = HashSet::new();
+
+    for (label, double) in [(first_label, false), (second_label, true)] {
+        let mut suffixes: HashMap<&[u32], String> = HashMap::new();
+
+        tables += &syntax.export(label);
+
+        for (index, rows) in char_rows.iter().enumerate() {
+            let name = format!("{label}_{index:02X}");
+            let shared = (0..glyph_height).find(|&i| suffixes.contains_key(&rows[i..]));
+
+            if shared == Some(0) {
+                tables += &syntax.word(&suffixes[&rows[..]]);
+                continue;
+            }
+            tables += &syntax.word(&name);
+
+            let end = shared.unwrap_or(glyph_height);
+            let mut segments = vec![];
+            for i in 0..end {
+                let segment = match i {
+                    0 => name.clone(),
+                    _ => format!("{name}_row{i}"),
+                };
+                suffixes.insert(&rows[i..], segment.clone());
+
+                let function = build_function(
+                    rows[i],
+                    glyph_width,
+                    double,
+                    pixel_format,
+                    stride,
+                    quirks,
+                    registers,
+                );
+                // without a base register the row function loads and steps past a full
+                // address before its stores, then jumps and advances after them
+                let stores = &function[2..function.len() - 2];
+                let mut body = syntax.body(stores);
+                instructions += stores.len();
+                // a quirk only replaces the store of a pair that has one
+                applied.extend(
+                    quirks
+                        .iter()
+                        .filter(|q| {
+                            u32::from(q.row) == rows[i]
+                                && q.double == double
+                                && q.has_store(glyph_width)
+                        })
+                        .map(|q| q.name.clone()),
+                );
+
+                if i + 1 == glyph_height {
+                    body += &syntax.body(&[Instr::Jr(Reg::Ra), advance]);
+                    instructions += 2;
+                } else if i + 1 == end {
+                    let target = suffixes[&rows[i + 1..]].clone();
+                    body += &format!("    j      {target}\n");
+                    body += &syntax.delay_slot(advance);
+                    instructions += 2;
+                    targets.insert(target);
+                } else {
+                    body += &syntax.body(&[advance]);
+                    instructions += 1;
+                }
+
+                segments.push((segment, body));
+            }
+
+            functions.push((name, segments));
+        }
+
+        tables += "\n";
+    }
+
+    let mut code = String::new();
+    for (name, segments) in functions {
+        code += &syntax.leaf(&name);
+        for (i, (segment, body)) in segments.into_iter().enumerate() {
+            // only rows that another glyph jumps into need a label
+            if i > 0 && targets.contains(&segment) {
+                code += &syntax.label(&segment);
+            }
+            code += &body;
+        }
+        code += &syntax.end(&name);
+        code += "\n";
+    }
+
+    let mut quirks = applied.into_iter().collect::<Vec<_>>();
+    quirks.sort();
+    timings::lap("codegen");
+
+    Ok(BuildArtifacts {
+        prologue: framing.prologue.to_string(),
+        tables,
+        functions: code,
+        entries: String::new(),
+        bin: vec![],
+        labels: vec![first_label.to_string(), second_label.to_string()],
+        table_size: 2 * char_rows.len() * size_of::<u32>(),
+        code_size: instructions * size_of::<u32>(),
+        quirks,
+        epilogue: framing.epilogue.to_string(),
+    })
+}
+
+/// Wrappers that save the next and color registers and start the row chain, so `row_end`'s
+/// `jr ra` returns to a C caller
+pub fn entry_source(options: &BuildOptions) -> String {
+    let BuildOptions {
+        first_label,
+        glyph_height,
+        pixel_format,
+        glyph_entries,
+        glyph_colors,
+        registers,
+        syntax,
+        ..
+    } = *options;
+    let Registers {
+        list,
+        framebuffer,
+        color,
+        next,
+        base,
+    } = registers;
+    let [list, framebuffer, color, next, a0, a1, a2, a3] = [
+        list,
+        framebuffer,
+        color,
+        next,
+        Reg::A0,
+        Reg::A1,
+        Reg::A2,
+        Reg::A3,
+    ]
+    .map(|r| syntax.reg(r));
+
+    let draw = format!("{first_label}_draw");
+    let pixel = pixel_format.c_type();
+    let mut rv = String::new();
+
+    rv += &syntax.comment(&format!(
+        "void {draw}(const u32 *block, {pixel} *dst, u32 color)"
+    ));
+    rv += &syntax.leaf(&draw);
+    rv += &syntax.push(registers.next);
+    rv += &syntax.push(registers.color);
+    match pixel_format {
+        PixelFormat::Rgba16 => rv += &format!("    move   {color}, {a2}\n"),
+        // sd stores all 64 bits of the color register, so it needs the color in both halves
+        PixelFormat::Rgba32 => {
+            rv += &format!("    dsll32 {color}, {a2}, 0\n");
+            rv += &format!("    dsll32 {a3}, {a2}, 0\n");
+            rv += &format!("    dsrl32 {a3}, {a3}, 0\n");
+            rv += &format!("    or     {color}, {color}, {a3}\n");
+        }
+    }
+    // Registers::check keeps these from overwriting arguments still to be moved
+    for (reg, argument) in [(&framebuffer, &a1), (&list, &a0)] {
+        if reg != argument {
+            rv += &format!("    move   {reg}, {argument}\n");
+        }
+    }
+    match base {
+        Some(base) => {
+            let base = syntax.reg(base);
+            rv += &format!("    la     {base}, {first_label}\n");
+            rv += &format!("    lhu    {next}, 0({list})\n");
+            rv += &format!("    addu   {next}, {next}, {base}\n");
+            rv += &format!("    jr     {next}\n");
+            rv += &format!("     addi  {list}, {list}, 2\n");
+        }
+        None => {
+            rv += &format!("    lw     {next}, 0({list})\n");
+            rv += &format!("    jr     {next}\n");
+            rv += &format!("     addi  {list}, {list}, 4\n");
+        }
+    }
+    rv += &syntax.end(&draw);
+    rv += "\n";
+
+    let block = (glyph_height + 1) * registers.entry_size();
+    for (index, (single, double)) in glyph_entries.iter().enumerate() {
+        for (kind, name) in [single, double].into_iter().enumerate() {
+            let offset = (index * 2 + kind) * block;
+            match glyph_colors {
+                Some(colors) => {
+                    let color = [colors.0, colors.1][kind];
+                    // sh and sw store the low half and both halves of the register, so repeat the
+                    // color
+                    let color = match pixel_format {
+                        PixelFormat::Rgba16 => color << 16 | color,
+                        PixelFormat::Rgba32 => color,
+                    };
+                    rv += &syntax.comment(&format!("void {name}_draw({pixel} *dst)"));
+                    rv += &syntax.leaf(&format!("{name}_draw"));
+                    rv += &format!("    li     {a2}, 0x{color:08X}\n");
+                }
+                None => {
+                    rv += &syntax.comment(&format!("void {name}_draw({pixel} *dst, u32 color)"));
+                    rv += &syntax.leaf(&format!("{name}_draw"));
+                    rv += &format!("    move   {a2}, {a1}\n");
+                }
+            }
+            rv += &format!("    move   {a1}, {a0}\n");
+            rv += &format!("    la     {a0}, {first_label} + 0x{offset:X}\n");
+            rv += &format!("    j      {draw}\n");
+            rv += "     nop\n";
+            rv += &syntax.end(&format!("{name}_draw"));
+            rv += "\n";
+        }
+    }
+
+    rv
+}
+
+/// The table entries as `.word` directives, or `.half` offsets from the first label with
+/// `registers.base`, with their exported labels
+pub fn table_source(
+    table: &[String],
+    (first_label, second_label): (&str, &str),
+    glyph_labels: &[(String, String)],
+    glyph_comments: &[String],
+    glyph_height: usize,
+    registers: Registers,
+    syntax: Syntax,
+) -> String {
+    let mut rv = String::new();
+
+    for (index, block) in table.chunks(glyph_height + 1).enumerate() {
+        match index {
+            0 => rv += &syntax.export(first_label),
+            1 => rv += &syntax.export(second_label),
+            _ => {}
+        }
+
+        if let Some(comment) = glyph_comments.get(index / 2) {
+            match index % 2 {
+                0 => rv += &syntax.comment(comment),
+                _ => rv += &syntax.comment(&format!("{comment} double")),
+            }
+        }
+
+        if let Some((single, double)) = glyph_labels.get(index / 2) {
+            let label = if index % 2 == 0 { single } else { double };
+            rv += &syntax.export(label);
+        }
+
+        for i in block {
+            match registers.base {
+                Some(_) => rv += &syntax.half(&format!("{i} - {first_label}")),
+                None => rv += &syntax.word(i),
+            }
+        }
+
+        rv += "\n";
+    }
+
+    rv
+}
+
+/// Linker script placing the tables of `object` at `vram`, providing their labels for objects
+/// that don't define them, such as a `--emit bin` table
+pub fn linker_script_source(
+    object: &str,
+    vram: u32,
+    first_label: &str,
+    second_label: &str,
+    glyph_height: usize,
+    registers: Registers,
+) -> String {
+    let second_offset = (glyph_height + 1) * registers.entry_size();
+    let mut rv = String::new();
+
+    rv += "SECTIONS\n{\n";
+    rv += &format!("    .{first_label} 0x{vram:08X} :\n    {{\n");
+    rv += &format!("        PROVIDE({first_label} = .);\n");
+    rv += &format!("        PROVIDE({second_label} = . + {second_offset});\n");
+    rv += &format!("        {object}(.text .data .rodata)\n");
+    rv += "    }\n}\n\n";
+
+    rv += &format!("EXTERN({first_label} {second_label})\n");
+    rv += &format!(
+        "ASSERT({first_label} == 0x{vram:08X}, \"{first_label} is not at 0x{vram:08X}\")\n"
+    );
+    rv += &format!(
+        "ASSERT({second_label} == {first_label} + {second_offset}, \"{second_label} does not follow {first_label}\")\n"
+    );
+
+    rv
+}
+
+/// C declarations of the exported tables, labels and entry points, with their sizes and the
+/// registers of the row functions' calling convention as macros; `u16` and `u32` come from the
+/// including file
+pub fn c_header_source(
+    options: &BuildOptions,
+    built: &BuildArtifacts,
+    num_chars: usize,
+    chained: bool,
+    guard: &str,
+) -> String {
+    let BuildOptions {
+        first_label,
+        second_label,
+        glyph_labels,
+        glyph_width,
+        glyph_height,
+        pixel_format,
+        stride,
+        entry,
+        glyph_entries,
+        glyph_colors,
+        shared,
+        registers,
+        ..
+    } = *options;
+    let Registers {
+        list,
+        framebuffer,
+        color,
+        next,
+        base,
+    } = registers;
+
+    let prefix = first_label.to_uppercase();
+    let pixel = pixel_format.c_type();
+    // words from one glyph's entry in a table to the next
+    let glyph_words = match chained {
+        true => 1,
+        false => 2 * (glyph_height + 1),
+    };
+    let entry_type = match registers.base {
+        Some(_) => "u16",
+        None => "u32",
+    };
+
+    let mut rv = String::new();
+
+    rv += &format!("#ifndef {guard}\n#define {guard}\n\n");
+
+    rv += &format!("#define {prefix}_NUM_CHARS {num_chars}\n");
+    rv += &format!("#define {prefix}_GLYPH_WIDTH {glyph_width}\n");
+    rv += &format!("#define {prefix}_GLYPH_HEIGHT {glyph_height}\n");
+    rv += &format!("#define {prefix}_GLYPH_WORDS {glyph_words}\n");
+    rv += &format!("#define {prefix}_STRIDE {stride}\n");
+    rv += &format!("#define {prefix}_TABLE_SIZE {}\n", built.table_size);
+    rv += &format!("#define {prefix}_CODE_SIZE {}\n\n", built.code_size);
+
+    rv += &format!(
+        "/* row functions read the next entry through {prefix}_REG_LIST, draw at\n \
+         * {prefix}_REG_FRAMEBUFFER in {prefix}_REG_COLOR and jump through {prefix}_REG_NEXT */\n"
+    );
+    for (name, reg) in [
+        ("LIST", list),
+        ("FRAMEBUFFER", framebuffer),
+        ("COLOR", color),
+        ("NEXT", next),
+    ] {
+        rv += &format!("#define {prefix}_REG_{name} \"{}\"\n", reg.name());
+    }
+    if let Some(base) = base {
+        rv += &format!(
+            "/* the entries are halfword offsets from {first_label}, which {prefix}_REG_BASE holds */\n"
+        );
+        rv += &format!("#define {prefix}_REG_BASE \"{}\"\n", base.name());
+    }
+    rv += "\n";
+
+    rv += &format!("extern {entry_type} {first_label}[];\n");
+    rv += &format!("extern {entry_type} {second_label}[];\n");
+    for (single, double) in glyph_labels {
+        rv += &format!("extern {entry_type} {single}[];\n");
+        rv += &format!("extern {entry_type} {double}[];\n");
+    }
+    for font in shared {
+        rv += &format!("extern {entry_type} {}[];\n", font.first_label);
+        rv += &format!("extern {entry_type} {}[];\n", font.second_label);
+    }
+    rv += "\n";
+
+    if entry || !glyph_entries.is_empty() {
+        rv += &format!("void {first_label}_draw(const u32 *block, {pixel} *dst, u32 color);\n");
+    }
+    let color = if glyph_colors.is_some() {
+        ""
+    } else {
+        ", u32 color"
+    };
+    for (single, double) in glyph_entries {
+        rv += &format!("void {single}_draw({pixel} *dst{color});\n");
+        rv += &format!("void {double}_draw({pixel} *dst{color});\n");
+    }
+    if entry || !glyph_entries.is_empty() {
+        rv += "\n";
+    }
+
+    rv += &format!("#endif /* {guard} */\n");
+
+    rv
+}
+
+/// A Rust module of the glyph bitmaps, with a safe wrapper around the `{first_label}_draw` entry
+/// point that checks each glyph fits the framebuffer slice it's drawn into
+pub fn rust_module_source(data: &[u8], options: &BuildOptions) -> String {
+    let BuildOptions {
+        first_label,
+        glyph_width,
+        glyph_height,
+        pixel_format,
+        stride,
+        ..
+    } = *options;
+
+    let rows = glyph_rows(data, glyph_width, glyph_height);
+    let (row, digits) = match glyph_width {
+        8 => ("u8", 2),
+        16 => ("u16", 4),
+        _ => ("u32", 8),
+    };
+    let (pixel, spread) = match pixel_format {
+        PixelFormat::Rgba16 => ("u16", "u32::from(color) * 0x0001_0001"),
+        PixelFormat::Rgba32 => ("u32", "color"),
+    };
+
+    let mut rv = String::new();
+
+    rv += &format!(
+        "//! The `{first_label}` font, drawn by the row functions built with `--entry`\n\n"
+    );
+    rv += "#![allow(non_upper_case_globals)]\n\n";
+
+    rv += &format!("pub const NUM_CHARS: usize = {};\n", rows.len());
+    rv += &format!("pub const GLYPH_WIDTH: usize = {glyph_width};\n");
+    rv += &format!("pub const GLYPH_HEIGHT: usize = {glyph_height};\n");
+    rv += "/// Pixels from one framebuffer row to the next\n";
+    rv += &format!(
+        "pub const STRIDE: usize = {};\n\n",
+        stride as usize / pixel_format.size()
+    );
+
+    rv += "/// The rows of each glyph, leftmost pixel in the top bit\n";
+    rv += &format!("pub static GLYPHS: [[{row}; GLYPH_HEIGHT]; NUM_CHARS] = [\n");
+    for glyph in &rows {
+        let glyph = glyph
+            .iter()
+            .map(|r| format!("0x{r:0digits$X}"))
+            .collect::<Vec<_>>();
+        rv += &format!("    [{}],\n", glyph.join(", "));
+    }
+    rv += "];\n\n";
+
+    rv += "/// Which of a glyph's table blocks to draw\n";
+    rv += "#[derive(Clone, Copy, PartialEq, Eq)]\n";
+    rv += "pub enum Block {\n";
+    rv += "    /// Every pixel stored on its own\n";
+    rv += "    Single,\n";
+    rv +=
+        "    /// Horizontal pairs of pixels stored together, which needs `dst` aligned to a pair\n";
+    rv += "    Double,\n";
+    rv += "}\n\n";
+
+    rv += "unsafe extern \"C\" {\n";
+    rv += &format!("    static {first_label}: [u32; NUM_CHARS * 2 * (GLYPH_HEIGHT + 1)];\n");
+    rv +=
+        &format!("    fn {first_label}_draw(block: *const u32, dst: *mut {pixel}, color: u32);\n");
+    rv += "}\n\n";
+
+    rv += "/// Draw glyph `index` in `color` with its top left pixel at `fb[offset]`\n";
+    rv += "///\n";
+    rv += "/// Panics if there's no such glyph, if it would run off the end of `fb`, or if `block` is\n";
+    rv += "/// [`Block::Double`] and the pixel at `offset` doesn't start an aligned pair.\n";
+    rv += &format!(
+        "pub fn draw(fb: &mut [{pixel}], offset: usize, index: usize, block: Block, color: {pixel}) {{\n"
+    );
+    rv += "    assert!(index < NUM_CHARS, \"no glyph {index}\");\n";
+    rv += "    let end = offset + (GLYPH_HEIGHT - 1) * STRIDE + GLYPH_WIDTH;\n";
+    rv += "    assert!(end <= fb.len(), \"glyph {index} at {offset} runs off the framebuffer\");\n";
+    rv += "    let dst = fb[offset..].as_mut_ptr();\n";
+    rv += &format!("    let aligned = dst.addr().is_multiple_of(2 * size_of::<{pixel}>());\n");
+    rv += "    assert!(block == Block::Single || aligned, \"unaligned pair at {offset}\");\n\n";
+    rv += "    let words = (index * 2 + block as usize) * (GLYPH_HEIGHT + 1);\n";
+    rv += "    // SAFETY: the blocks only store to the checked rows of fb and read the table\n";
+    rv += "    unsafe {\n";
+    rv += &format!("        let table = (&raw const {first_label}).cast::<u32>();\n");
+    rv += &format!("        {first_label}_draw(table.add(words), dst, {spread});\n");
+    rv += "    }\n";
+    rv += "}\n";
+
+    rv
+}
+
+/// CSV of the instructions, stores and pixels of each function, then of each glyph block as the
+/// sum of the functions it runs
+pub fn stats_source(functions: &[Function], table: &[String], options: &BuildOptions) -> String {
+    let BuildOptions {
+        first_label,
+        second_label,
+        glyph_labels,
+        glyph_height,
+        pixel_format,
+        registers,
+        ..
+    } = *options;
+
+    let pixels = |instr: &Instr| instr.pixels(pixel_format, registers.framebuffer);
+    let count = |body: &[Instr]| {
+        (
+            body.len(),
+            body.iter().filter(|i| pixels(i) > 0).count(),
+            body.iter().map(pixels).sum::<usize>(),
+        )
+    };
+
+    let mut rv = String::from("kind,name,instructions,stores,pixels\n");
+    let mut counts = HashMap::new();
+
+    for function in functions {
+        let (instructions, stores, pixels) = count(function.body());
+        counts.insert(function.name(), (instructions, stores, pixels));
+        rv += &format!(
+            "function,{},{instructions},{stores},{pixels}\n",
+            function.name()
+        );
+    }
+
+    for (index, block) in table.chunks(glyph_height + 1).enumerate() {
+        let name = match glyph_labels.get(index / 2) {
+            Some((single, double)) => [single, double][index % 2].clone(),
+            None => {
+                let label = [first_label, second_label][index % 2];
+                format!("{label}_{:02X}", index / 2)
+            }
+        };
+
+        let (mut instructions, mut stores, mut pixels) = (0, 0, 0);
+        for entry in block {
+            let (i, s, p) = counts[entry.as_str()];
+            instructions += i;
+            stores += s;
+            pixels += p;
+        }
+        rv += &format!("glyph,{name},{instructions},{stores},{pixels}\n");
+    }
+
+    rv
+}
+
+/// One function read by [`parse_function`]
+pub enum Parsed {
+    /// The pixels of a row function and how many bytes it advances a1 by
+    Row(Box<[u8]>, i32),
+    End,
+    Unknown,
+}
+
+/// A font read back out of a binary by [`extract`]
+pub struct Extraction {
+    /// Address of the table
+    pub vram: u32,
+    /// Address of the code after the table
+    pub code: u32,
+    pub glyph_width: usize,
+    pub glyph_height: usize,
+    /// Whether each block of the table ends in an entry for `row_end`
+    pub row_end: bool,
+    /// Index in the table of the first glyph read
+    pub first: usize,
+    pub font: Vec<u8>,
+    pub extra: Vec<u8>,
+    pub table: Vec<String>,
+    pub addresses: Vec<u32>,
+    pub functions: Vec<FunctionInfo>,
+    /// Bytes between framebuffer rows, if every glyph row function agrees on it
+    pub stride: Option<i16>,
+    /// Function names and `.word` padding from the extra offset on, in address order
+    pub extra_layout: Vec<String>,
+    /// Table entries that point at something other than a row function or `row_end`, or at the
+    /// wrong one of them for their place in the table
+    pub anomalies: Vec<String>,
+}
+
+/// A function found while extracting
+pub struct FunctionInfo {
+    pub address: u32,
+    pub name: String,
+    pub referenced: bool,
+    /// Bytes of machine code
+    pub size: u32,
+    /// Whether it's at or after the extra offset
+    pub extra: bool,
+}
+
+struct RowDecoder {
+    pixels: Vec<u8>,
+    format: PixelFormat,
+    registers: Registers,
+    next: bool,
+    /// Whether the halfword offset loaded into next has had the base added
+    rebased: bool,
+    advance: bool,
+    delta: i32,
+    delay_slot: bool,
+}
+
+enum Step {
+    More,
+    Done,
+    Invalid,
+    /// A store to this byte of the row, which is outside its pixels
+    OutOfRange(i32),
+}
+
+impl RowDecoder {
+    fn new(width: usize, format: PixelFormat, registers: Registers) -> Self {
+        Self {
+            pixels: vec![0; width],
+            format,
+            registers,
+            next: false,
+            rebased: false,
+            advance: false,
+            delta: 0,
+            delay_slot: false,
+        }
+    }
+
+    // the instructions can come in any order, so long as the delay slot is last
+    fn step(&mut self, instr: Option<Instr>) -> Step {
+        if let Some((offset, count)) = self.format.store(instr, self.registers) {
+            let size = self.format.size() as i32;
+            let offset = offset as i32 + self.delta;
+            if offset < 0 || offset + count as i32 * size > self.pixels.len() as i32 * size {
+                return Step::OutOfRange(offset);
+            }
+            // pair stores decode darker, telling them apart from two single stores
+            let shades = Shades::default();
+            let value = if count == 2 {
+                shades.pair
+            } else {
+                shades.single
+            };
+            let first = (offset / size) as usize;
+            self.pixels[first..first + count].fill(value);
+            return self.next_step();
+        }
+
+        let Registers {
+            list,
+            framebuffer,
+            next,
+            base,
+            ..
+        } = self.registers;
+        let entry_size = self.registers.entry_size() as i16;
+        match (instr, base) {
+            (Some(Instr::Lw(rt, 0, rs)), None) | (Some(Instr::Lhu(rt, 0, rs)), Some(_))
+                if (rt, rs) == (next, list) =>
+            {
+                self.next = true
+            }
+            (Some(Instr::Addu(rd, rs, rt)), Some(base))
+                if self.next && rd == next && [(next, base), (base, next)].contains(&(rs, rt)) =>
+            {
+                self.rebased = true
+            }
+            (Some(Instr::Addi(rt, rs, n) | Instr::Addiu(rt, rs, n)), _)
+                if (rt, rs) == (list, list) && n == entry_size =>
+            {
+                self.advance = true
+            }
+            (Some(Instr::Addi(rt, rs, n) | Instr::Addiu(rt, rs, n)), _)
+                if (rt, rs) == (framebuffer, framebuffer) =>
+            {
+                self.delta += n as i32
+            }
+            (Some(Instr::Jr(rs)), _) if rs == next && !self.delay_slot => {
+                self.delay_slot = true;
+                return Step::More;
+            }
+            (Some(Instr::Nop), _) if self.delay_slot => {}
+            _ => return Step::Invalid,
+        }
+
+        self.next_step()
+    }
+
+    fn next_step(&self) -> Step {
+        if self.delay_slot {
+            Step::Done
+        } else {
+            Step::More
+        }
+    }
+
+    fn finish(self) -> Parsed {
+        let rebased = self.rebased || self.registers.base.is_none();
+        if !(self.next && rebased && self.advance) {
+            return Parsed::Unknown;
+        }
+
+        Parsed::Row(self.pixels.into_boxed_slice(), self.delta)
+    }
+}
+
+/// Read one row function of `width` pixels of `format` or `row_end` at the cursor, leaving it
+/// after the function; the cursor's data is loaded at `vram`
+pub fn parse_function<T>(
+    cursor: &mut Cursor<T>,
+    vram: u32,
+    width: usize,
+    format: PixelFormat,
+    registers: Registers,
+) -> Result<Parsed>
+where
+    Cursor<T>: ReadBytesExt,
+{
+    let start = cursor.position();
+
+    // a function cut off by the end of the input is just trailing data
+    let unknown = |cursor: &mut Cursor<T>| {
+        cursor.set_position(start + size_of::<u32>() as u64);
+        Ok(Parsed::Unknown)
+    };
+
+    let (Ok(first), Ok(second)) = (cursor.read_u32::<BE>(), cursor.read_u32::<BE>()) else {
+        return unknown(cursor);
+    };
+    let row_end = mips::row_end(registers.color, registers.next);
+    if (first, second) == (row_end[0].encode(), row_end[1].encode()) {
+        // consume epilogue
+        cursor.read_u32::<BE>()?;
+        cursor.read_u32::<BE>()?;
+        cursor.read_u32::<BE>()?;
+        cursor.read_u32::<BE>()?;
+        return Ok(Parsed::End);
+    }
+    cursor.set_position(start);
+
+    let mut decoder = RowDecoder::new(width, format, registers);
+
+    loop {
+        let Ok(word) = cursor.read_u32::<BE>() else {
+            return unknown(cursor);
+        };
+        let instr = Instr::decode(word);
+        match decoder.step(instr) {
+            Step::More => {}
+            Step::Done => break,
+            Step::Invalid => return unknown(cursor),
+            Step::OutOfRange(offset) => bail!(Error::StoreOutOfRange {
+                function: vram.wrapping_add(start as u32),
+                address: vram.wrapping_add((cursor.position() - size_of::<u32>() as u64) as u32),
+                // only stores are out of range
+                instr: format!("{:1}", instr.unwrap()),
+                offset,
+                width,
+            }),
+        }
+    }
+
+    Ok(decoder.finish())
+}
+
+/// Where a font's table is and how its glyphs are laid out
+#[derive(Clone, Copy)]
+pub struct ExtractOptions {
+    /// Address of the table, which the data starts with
+    pub vram: u32,
+    /// Glyphs in the table
+    pub num_chars: usize,
+    /// Glyph to start reading at; the entries of the glyphs before it aren't read
+    pub first: usize,
+    /// Glyphs to read from `first` on
+    pub count: usize,
+    /// Bytes from the end of the table to the first function that no glyph row uses
+    pub extra_offset: usize,
+    /// Pixels per glyph row
+    pub glyph_width: usize,
+    /// Rows per glyph
+    pub glyph_height: usize,
+    /// Whether each block of the table ends in an entry for `row_end`
+    pub row_end: bool,
+    /// How the framebuffer the row functions draw to stores a pixel
+    pub pixel_format: PixelFormat,
+    /// Registers the row functions keep their state in
+    pub registers: Registers,
+}
+
+impl ExtractOptions {
+    /// Entries in each of a glyph's two table blocks
+    pub fn block(self) -> usize {
+        self.glyph_height + usize::from(self.row_end)
+    }
+}
+
+/// The number of glyphs in the table at the start of `data`, linked at `vram`, which runs up to
+/// the lowest address its entries point at, where the code starts
+pub fn infer_num_chars(
+    data: &[u8],
+    vram: u32,
+    glyph_height: usize,
+    row_end: bool,
+    chained: bool,
+    registers: Registers,
+) -> Result<usize> {
+    let end = u64::from(vram) + data.len() as u64;
+    let mut code = end;
+    let mut table = vec![];
+    let entry_size = match chained {
+        true => size_of::<u32>(),
+        false => registers.entry_size(),
+    };
+    let table_registers = Registers {
+        base: registers.base.filter(|_| !chained),
+        ..registers
+    };
+
+    let entries = table_registers.entries(data, vram);
+    for (index, word) in entries.into_iter().enumerate() {
+        let address = u64::from(vram) + (index * entry_size) as u64;
+        if address >= code {
+            break;
+        }
+        if u64::from(word) <= address || u64::from(word) >= end {
+            bail!(
+                "word {index} of the table at 0x{vram:08X} is 0x{word:08X}, which isn't an \
+                 address in the code after it"
+            );
+        }
+        code = code.min(word.into());
+        table.push(word);
+    }
+
+    // chained tables are a pointer per glyph for each of the single and double tables
+    let block = glyph_height + usize::from(row_end);
+    let glyph_words = match chained {
+        true => 2,
+        false => 2 * block,
+    };
+    if table.is_empty() || !table.len().is_multiple_of(glyph_words) {
+        bail!(
+            "the {}-word table at 0x{vram:08X} isn't a whole number of {glyph_words}-word glyphs; \
+             check --glyph-height",
+            table.len()
+        );
+    }
+    if !chained && row_end {
+        let mut ends = table.chunks_exact(block).map(|b| b[glyph_height]);
+        let first = ends.next();
+        if ends.any(|e| Some(e) != first) {
+            bail!(
+                "the blocks of the table at 0x{vram:08X} don't all end in row_end; check \
+                 --glyph-height"
+            );
+        }
+    }
+
+    Ok(table.len() / glyph_words)
+}
+
+/// The extra offset of a table whose extra lines follow every row function a glyph uses: the
+/// end of the last of those functions. `options.extra_offset` is ignored
+pub fn infer_extra_offset(data: &[u8], options: &ExtractOptions) -> Result<usize> {
+    let ExtractOptions {
+        vram,
+        num_chars,
+        glyph_width,
+        pixel_format,
+        registers,
+        ..
+    } = *options;
+    let offsets_len = num_chars * options.block() * registers.entry_size() * 2;
+
+    let Some(table_data) = data.get(..offsets_len) else {
+        bail!(Error::TruncatedTable {
+            vram,
+            num_chars,
+            needed: offsets_len,
+            available: data.len(),
+        });
+    };
+    let data_vram = vram.wrapping_add(offsets_len as u32);
+    let code_len = data.len() - offsets_len;
+
+    let mut cursor = Cursor::new(&data[offsets_len..]);
+    let mut rv = 0;
+    for address in registers.entries(table_data, vram) {
+        // extract reports entries outside the code
+        let Some(offset) = address
+            .checked_sub(data_vram)
+            .filter(|&o| (o as usize) < code_len)
+        else {
+            continue;
+        };
+        cursor.set_position(offset.into());
+        if let Parsed::Row(..) =
+            parse_function(&mut cursor, data_vram, glyph_width, pixel_format, registers)?
+        {
+            rv = rv.max(cursor.position() as usize);
+        }
+    }
+
+    Ok(rv)
+}
+
+/// Read a font back out of a pointer table and the row functions following it
+pub fn extract(data: &[u8], options: &ExtractOptions) -> Result<Extraction> {
+    let ExtractOptions {
+        vram,
+        num_chars,
+        first,
+        count,
+        extra_offset,
+        glyph_width,
+        glyph_height,
+        row_end,
+        pixel_format,
+        registers,
+    } = *options;
+    let block = options.block();
+    let offsets_len = num_chars * block * registers.entry_size() * 2;
+
+    let Some(table_data) = data.get(..offsets_len) else {
+        bail!(Error::TruncatedTable {
+            vram,
+            num_chars,
+            needed: offsets_len,
+            available: data.len(),
+        });
+    };
+    let Some(data_vram) = u32::try_from(offsets_len)
+        .ok()
+        .and_then(|len| vram.checked_add(len))
+    else {
+        bail!("a table of {num_chars} glyphs at 0x{vram:08X} runs past the address space");
+    };
+    let code_len = data.len() - offsets_len;
+
+    if extra_offset > code_len {
+        bail!("extra offset 0x{extra_offset:X} is past the end of the code (0x{code_len:X} bytes)");
+    }
+    if first + count > num_chars {
+        bail!("{count} glyphs from 0x{first:02X} run past the end of a table of {num_chars}");
+    }
+
+    let skipped = first * block * 2;
+    let addresses = registers
+        .entries(table_data, vram)
+        .into_iter()
+        .skip(skipped)
+        .take(count * block * 2)
+        .collect::<Vec<_>>();
+    let mut anomalies = vec![];
+
+    // entries that can't be a function are reported and read as blank rows
+    let mut offsets = vec![];
+    for (index, &address) in addresses.iter().enumerate() {
+        let index = skipped + index;
+        let (glyph, kind, word) = (
+            index / (block * 2),
+            ["single", "double"][index / block % 2],
+            index % block,
+        );
+        offsets.push(match address.checked_sub(data_vram) {
+            Some(offset) if (offset as usize) < code_len && offset % 4 == 0 => Some(offset),
+            Some(offset) if (offset as usize) < code_len => {
+                anomalies.push(format!(
+                    "glyph 0x{glyph:02X} {kind} word {word} is 0x{address:08X}, which isn't \
+                     word-aligned, so it can't be a function"
+                ));
+                None
+            }
+            _ => {
+                anomalies.push(format!(
+                    "glyph 0x{glyph:02X} {kind} word {word} is 0x{address:08X}, outside the code \
+                     at 0x{data_vram:08X}..0x{:08X}; check --vram and the glyph count",
+                    data_vram as usize + code_len
+                ));
+                None
+            }
+        });
+    }
+
+    timings::lap("table walk");
+
+    let mut cursor = Cursor::new(&data[offsets_len..]);
+
+    let mut font: Vec<u8> = vec![];
+    let mut strides = vec![];
+    let mut table = vec![];
+    let mut patched = HashSet::new();
+
+    for (glyph, chunk) in offsets
+        .chunks(block)
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .enumerate()
+    {
+        let glyph = first + glyph;
+        if let [block, _] = chunk {
+            for (row, offset) in block[..glyph_height].iter().enumerate() {
+                let Some(offset) = offset else {
+                    font.extend(std::iter::repeat_n(0, glyph_width));
+                    continue;
+                };
+                cursor.set_position(*offset as u64);
+                let context = || {
+                    format!(
+                        "reading glyph 0x{glyph:02X} row {row} at 0x{:08X}",
+                        offset + data_vram
+                    )
+                };
+                match parse_function(&mut cursor, data_vram, glyph_width, pixel_format, registers)
+                    .with_context(context)?
+                {
+                    Parsed::Row(l, stride) => {
+                        font.extend(l.iter());
+                        strides.push(stride);
+                    }
+                    // the pass over both blocks below reports these, and the row is left blank
+                    Parsed::End | Parsed::Unknown => {
+                        font.extend(std::iter::repeat_n(0, glyph_width))
+                    }
+                }
+            }
+        }
+
+        let start = table.len();
+        for (kind, block) in ["single", "double"].iter().zip(chunk) {
+            for (word, offset) in block.iter().enumerate() {
+                // the table so far has a name for every entry before this one
+                let Some(offset) = offset else {
+                    table.push(format!("0x{:08X}", addresses[table.len()]));
+                    continue;
+                };
+                cursor.set_position(*offset as u64);
+                let context = || {
+                    format!(
+                        "reading glyph 0x{glyph:02X} {kind} word {word} at 0x{:08X}",
+                        offset + data_vram
+                    )
+                };
+                table.push(
+                    match parse_function(
+                        &mut cursor,
+                        data_vram,
+                        glyph_width,
+                        pixel_format,
+                        registers,
+                    )
+                    .with_context(context)?
+                    {
+                        Parsed::Row(l, _) => {
+                            let b = l.iter().fold(0, |b, i| (b << 1) | (*i != 0) as u32);
+                            let name = row_name(kind, b, glyph_width);
+                            let expected = expected_shades(b, glyph_width, *kind == "double");
+                            if *l != expected[..] && patched.insert(*offset) {
+                                anomalies.push(format!(
+                                    "{name} at 0x{:08X} stores {}, not {}, as a hand patch would",
+                                    offset + data_vram,
+                                    show_shades(&l),
+                                    show_shades(&expected)
+                                ));
+                            }
+                            if row_end && word == glyph_height {
+                                anomalies.push(format!(
+                                    "glyph 0x{glyph:02X} {kind} word {word} is {name}, not \
+                                     row_end, so the glyph runs on into the next one"
+                                ));
+                            }
+                            name
+                        }
+                        Parsed::End if word < glyph_height => {
+                            anomalies.push(format!(
+                                "glyph 0x{glyph:02X} {kind} word {word} is row_end, which ends \
+                                 the glyph {} rows early",
+                                glyph_height - word
+                            ));
+                            "row_end".to_string()
+                        }
+                        Parsed::End => "row_end".to_string(),
+                        Parsed::Unknown => {
+                            let address = offset + data_vram;
+                            cursor.set_position(*offset as u64);
+                            let instr = match cursor.read_u32::<BE>().ok() {
+                                Some(w) => match Instr::decode(w) {
+                                    Some(instr) => format!("{instr:1}"),
+                                    None => format!(".word 0x{w:08X}"),
+                                },
+                                None => "the end of the input".to_string(),
+                            };
+                            anomalies.push(format!(
+                                "glyph 0x{glyph:02X} {kind} word {word} points at 0x{address:08X} \
+                                 (code offset 0x{offset:X}), which starts with {instr} and isn't \
+                                 a row function or row_end"
+                            ));
+                            format!("0x{address:08X}")
+                        }
+                    },
+                );
+            }
+        }
+
+        // the double block draws the same rows as the single one, which gives the pixels
+        if let [single, double] = table[start..].chunks(block).collect::<Vec<_>>()[..] {
+            for (row, (single, double)) in single.iter().zip(double).enumerate() {
+                let single = single.strip_prefix("row_single_");
+                let double = double.strip_prefix("row_double_");
+                if let (Some(single), Some(double)) = (single, double)
+                    && single != double
+                {
+                    anomalies.push(format!(
+                        "glyph 0x{glyph:02X} row {row} is {single} in the single block but \
+                         {double} in the double block"
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut extra = vec![];
+
+    cursor.set_position(extra_offset as u64);
+    while (cursor.position() as usize) < data.len() - offsets_len {
+        if let Parsed::Row(l, _) =
+            parse_function(&mut cursor, data_vram, glyph_width, pixel_format, registers)?
+        {
+            extra.extend(l.iter());
+        }
+    }
+
+    let mut named = HashMap::new();
+    for (name, address) in table.iter().zip(&addresses) {
+        named.insert(*address, name.clone());
+    }
+
+    let mut functions: Vec<FunctionInfo> = vec![];
+    let mut kind = "single";
+
+    cursor.set_position(0);
+    while (cursor.position() as usize) < data.len() - offsets_len {
+        let address = data_vram + cursor.position() as u32;
+        let name =
+            match parse_function(&mut cursor, data_vram, glyph_width, pixel_format, registers)? {
+                Parsed::Row(l, _) => {
+                    let b = l.iter().fold(0, |b, i| (b << 1) | (*i != 0) as u32);
+                    if let Some(name) = named.get(&address) {
+                        name.clone()
+                    } else {
+                        // sw stores both pixels of a pair, so only double functions have them
+                        // paired up; otherwise assume the grouping of the previous function
+                        let Shades { single, pair } = Shades::default();
+                        let pairs = l.chunks_exact(2).collect::<Vec<_>>();
+                        if pairs.contains(&[pair, pair].as_slice()) {
+                            kind = "double";
+                        } else if pairs.contains(&[single, single].as_slice()) {
+                            kind = "single";
+                        }
+                        let name = row_name(kind, b, glyph_width);
+                        if functions.iter().any(|f| f.name == name) {
+                            kind = if kind == "single" { "double" } else { "single" };
+                            row_name(kind, b, glyph_width)
+                        } else {
+                            name
+                        }
+                    }
+                }
+                Parsed::End => "row_end".to_string(),
+                Parsed::Unknown => continue,
+            };
+
+        if name.starts_with("row_single_") {
+            kind = "single";
+        } else if name.starts_with("row_double_") {
+            kind = "double";
+        }
+
+        functions.push(FunctionInfo {
+            address,
+            referenced: named.contains_key(&address),
+            name,
+            size: data_vram + cursor.position() as u32 - address,
+            extra: address - data_vram >= extra_offset as u32,
+        });
+    }
+
+    let stride = strides
+        .first()
+        .filter(|&&first| strides.iter().all(|&s| s == first))
+        .and_then(|&s| i16::try_from(s).ok());
+
+    // the extra region word by word, up to the last function and the zeros padding it out
+    let names = functions
+        .iter()
+        .map(|f| (f.address, f.name.as_str()))
+        .collect::<HashMap<_, _>>();
+    let mut extra_layout = vec![];
+    let mut last = 0;
+    cursor.set_position(extra_offset as u64);
+    while (cursor.position() as usize) < data.len() - offsets_len {
+        let address = data_vram + cursor.position() as u32;
+        match names.get(&address) {
+            Some(name) => {
+                parse_function(&mut cursor, data_vram, glyph_width, pixel_format, registers)?;
+                extra_layout.push(name.to_string());
+                last = extra_layout.len();
+            }
+            None => extra_layout.push(format!(".word 0x{:08X}", cursor.read_u32::<BE>()?)),
+        }
+    }
+    let padding = extra_layout[last..]
+        .iter()
+        .take_while(|e| *e == ".word 0x00000000")
+        .count();
+    extra_layout.truncate(last + padding);
+    timings::lap("function parsing");
+
+    Ok(Extraction {
+        vram,
+        code: data_vram,
+        glyph_width,
+        glyph_height,
+        row_end,
+        first,
+        font,
+        extra,
+        table,
+        addresses,
+        functions,
+        stride,
+        extra_layout,
+        anomalies,
+    })
+}
+
+/// The pixels the generated row function for `row` decodes to, with pixels stored in pairs in
+/// the pair shade
+fn expected_shades(row: u32, width: usize, double: bool) -> Vec<u8> {
+    let Shades { single, pair } = Shades::default();
+    let set = |x: usize| (row >> (width - 1 - x)) & 1 != 0;
+    (0..width)
+        .map(|x| match (set(x), double && set(x & !1) && set(x | 1)) {
+            (false, _) => 0,
+            (true, true) => pair,
+            (true, false) => single,
+        })
+        .collect()
+}
+
+/// A row of pixels as `#` for single stores, `+` for pair stores and `.` for nothing
+fn show_shades(row: &[u8]) -> String {
+    row.iter()
+        .map(|&p| match p {
+            0 => '.',
+            _ if p == Shades::default().pair => '+',
+            _ => '#',
+        })
+        .collect()
+}
+
+/// Follow one glyph function of the chained layout, returning its pixels
+pub fn parse_chained(
+    data: &[u8],
+    vram: u32,
+    entry: u32,
+    glyph_width: usize,
+    glyph_height: usize,
+    pixel_format: PixelFormat,
+    registers: Registers,
+) -> Result<Vec<u8>> {
+    let Registers { framebuffer, .. } = registers;
+    let mut pixels = vec![];
+    let mut row = vec![0; glyph_width];
+    let mut pc = entry;
+
+    let fetch = |pc: u32| -> Result<Option<Instr>> {
+        let offset = pc.wrapping_sub(vram) as usize;
+        let Some(bytes) = data.get(offset..offset + size_of::<u32>()) else {
+            bail!("glyph at 0x{entry:08X} runs outside the input at 0x{pc:08X}");
+        };
+        Ok(Instr::decode(u32::from_be_bytes(bytes.try_into()?)))
+    };
+
+    while pixels.len() < glyph_width * glyph_height {
+        let instr = fetch(pc)?;
+
+        if let Some((offset, count)) = pixel_format.store(instr, registers) {
+            let first = offset as usize / pixel_format.size();
+            let Some(pixels) = row.get_mut(first..first + count).filter(|_| offset >= 0) else {
+                bail!("store to offset {offset} at 0x{pc:08X} is outside the glyph");
+            };
+            let shades = Shades::default();
+            pixels.fill(if count == 2 {
+                shades.pair
+            } else {
+                shades.single
+            });
+            pc += size_of::<u32>() as u32;
+            continue;
+        }
+
+        let advances = |instr: Option<Instr>| {
+            matches!(
+                instr,
+                Some(Instr::Addi(rt, rs, _) | Instr::Addiu(rt, rs, _))
+                    if (rt, rs) == (framebuffer, framebuffer)
+            )
+        };
+        match instr {
+            _ if advances(instr) => {
+                pixels.extend(std::mem::replace(&mut row, vec![0; glyph_width]));
+            }
+            Some(jump @ (Instr::J(_) | Instr::Jr(Reg::Ra))) => {
+                if !advances(fetch(pc + 4)?) {
+                    bail!("jump at 0x{pc:08X} doesn't advance a row in its delay slot");
+                }
+                pixels.extend(std::mem::replace(&mut row, vec![0; glyph_width]));
+
+                match jump {
+                    Instr::J(target) => {
+                        pc = ((pc + 4) & 0xF0000000) | target;
+                        continue;
+                    }
+                    _ if pixels.len() == glyph_width * glyph_height => break,
+                    _ => bail!(
+                        "glyph at 0x{entry:08X} returns after {} rows",
+                        pixels.len() / glyph_width
+                    ),
+                }
+            }
+            _ => bail!("unexpected instruction at 0x{pc:08X} in glyph at 0x{entry:08X}"),
+        }
+
+        pc += size_of::<u32>() as u32;
+    }
+
+    Ok(pixels)
+}
+
+/// Extract the chained layout: one pointer per glyph to single and then double functions
+pub fn extract_chained(
+    data: &[u8],
+    vram: u32,
+    num_chars: usize,
+    glyph_width: usize,
+    glyph_height: usize,
+    pixel_format: PixelFormat,
+    registers: Registers,
+) -> Result<Extraction> {
+    let table_len = num_chars * 2 * size_of::<u32>();
+    let Some(table) = data.get(..table_len) else {
+        bail!("the input is too short for a table of {num_chars} glyphs");
+    };
+
+    let addresses = table
+        .chunks_exact(size_of::<u32>())
+        .map(|w| u32::from_be_bytes(w.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    timings::lap("table walk");
+
+    let mut font = vec![];
+    for &entry in &addresses[..num_chars] {
+        let glyph = parse_chained(
+            data,
+            vram,
+            entry,
+            glyph_width,
+            glyph_height,
+            pixel_format,
+            registers,
+        )?;
+        font.extend(glyph);
+    }
+    timings::lap("function parsing");
+
+    Ok(Extraction {
+        vram,
+        code: vram + table_len as u32,
+        glyph_width,
+        glyph_height,
+        row_end: false,
+        first: 0,
+        font,
+        extra: vec![],
+        table: addresses.iter().map(|a| format!("0x{a:08X}")).collect(),
+        addresses,
+        functions: vec![],
+        stride: None,
+        extra_layout: vec![],
+        anomalies: vec![],
+    })
+}
+
+/// Glyphs and build options recovered from a generated `.s` file
+pub struct Recovered {
+    pub font: Vec<u8>,
+    pub extra: Vec<u8>,
+    pub quirks: Vec<Quirk>,
+    pub first_label: String,
+    pub second_label: String,
+    pub glyph_labels: Vec<(String, String)>,
+    /// The last comment above each glyph's single block, if every glyph has one
+    pub glyph_comments: Vec<String>,
+    pub glyph_width: usize,
+    pub glyph_height: usize,
+    /// How the framebuffer stores the pixels, going by the stores
+    pub pixel_format: PixelFormat,
+    /// Bytes the row functions advance by
+    pub stride: i16,
+    pub entry: bool,
+    pub glyph_entries: Vec<(String, String)>,
+    pub glyph_colors: Option<(u32, u32)>,
+    pub order: Vec<String>,
+}
+
+/// Rebuild the inputs of a generated `.s` file whose row functions use `registers`
+pub fn recover(source: &asm::Source, registers: Registers) -> Result<Recovered> {
+    let mut rows = HashMap::new();
+    let mut stride = None;
+
+    // row function names spell out every pixel of the row
+    let glyph_width = source
+        .functions
+        .iter()
+        .find_map(|(name, _)| name.strip_prefix("row_single_"))
+        .map_or(8, str::len);
+
+    // 16-bit pixels are stored with sh and 32-bit ones never are
+    let stores = |f: fn(&Instr) -> bool| source.functions.iter().any(|(_, b)| b.iter().any(f));
+    let pixel_format = if stores(|i| matches!(i, Instr::Sh(..))) {
+        PixelFormat::Rgba16
+    } else if stores(|i| matches!(i, Instr::Sw(..) | Instr::Sd(..))) {
+        PixelFormat::Rgba32
+    } else {
+        PixelFormat::default()
+    };
+
+    for (name, body) in &source.functions {
+        if name == "row_end" {
+            continue;
+        }
+
+        let mut decoder = RowDecoder::new(glyph_width, pixel_format, registers);
+        let mut done = false;
+        for (index, instr) in body.iter().enumerate() {
+            match decoder.step(Some(*instr)) {
+                Step::More => {}
+                Step::Done if index == body.len() - 1 => done = true,
+                _ => bail!("{name} is not a row function (instruction {index})"),
+            }
+        }
+
+        match decoder.finish() {
+            Parsed::Row(pixels, delta) if done => {
+                if rows.insert(name.as_str(), pixels).is_some() {
+                    bail!("{name} is defined twice");
+                }
+                match (stride, i16::try_from(delta)) {
+                    (None, Ok(delta)) => stride = Some(delta),
+                    (Some(s), Ok(delta)) if s == delta => {}
+                    _ => bail!("{name} advances by {delta} bytes, unlike the other row functions"),
+                }
+            }
+            _ => bail!("{name} is not a row function"),
+        }
+    }
+
+    // each block ends at the first row_end
+    let Some(glyph_height) = source.table.iter().position(|e| e == "row_end") else {
+        bail!("the table never references row_end");
+    };
+    let block = glyph_height + 1;
+
+    if source.table.is_empty() || !source.table.len().is_multiple_of(block * 2) {
+        bail!(
+            "the table has {} entries, which isn't a whole number of glyphs",
+            source.table.len()
+        );
+    }
+
+    let bits = |pixels: &[u8]| pixels.iter().fold(0, |b, i| (b << 1) | (*i != 0) as u32);
+    let row = |entry: &str| match rows.get(entry) {
+        Some(pixels) => Ok(pixels),
+        None => bail!("table entry {entry} is not a row function"),
+    };
+
+    let mut font = vec![];
+    for (index, glyph) in source.table.chunks_exact(block * 2).enumerate() {
+        let (single, double) = glyph.split_at(block);
+        for block in [single, double] {
+            if block[glyph_height] != "row_end" {
+                bail!(
+                    "glyph 0x{index:02X} ends with {} instead of row_end",
+                    block[glyph_height]
+                );
+            }
+        }
+
+        for (a, b) in single[..glyph_height].iter().zip(&double[..glyph_height]) {
+            let (a, b) = (row(a)?, row(b)?);
+            if bits(a) != bits(b) {
+                bail!("glyph 0x{index:02X} has different single and double rows");
+            }
+            font.extend(a.iter());
+        }
+    }
+
+    let label = |word: usize| {
+        source
+            .labels
+            .iter()
+            .find(|(i, _)| *i == word)
+            .map(|(_, label)| label.clone())
+    };
+    let (Some(first_label), Some(second_label)) = (label(0), label(block)) else {
+        bail!("the table doesn't export its first and second labels");
+    };
+
+    if let Some((word, label)) = source.labels.iter().find(|(i, _)| i % block != 0) {
+        bail!("{label} points into the middle of a table block (word {word})");
+    }
+
+    let others = source
+        .labels
+        .iter()
+        .filter(|(_, label)| *label != first_label && *label != second_label)
+        .collect::<Vec<_>>();
+    let mut glyph_labels = vec![];
+    if !others.is_empty() {
+        for index in 0..source.table.len() / (block * 2) {
+            let find = |word: usize| match others
+                .iter()
+                .filter(|(i, _)| *i == word)
+                .collect::<Vec<_>>()[..]
+            {
+                [(_, label)] => Ok(label.clone()),
+                _ => bail!("glyph 0x{index:02X} doesn't have exactly one label per block"),
+            };
+            glyph_labels.push((find(index * block * 2)?, find(index * block * 2 + block)?));
+        }
+    }
+
+    let glyph_comments = (0..source.table.len() / (block * 2))
+        .map(|index| {
+            source
+                .comments
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == index * block * 2)
+                .map(|(_, comment)| comment.clone())
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+
+    let base = base_rows(glyph_width);
+    let mut extra = vec![];
+    for (name, _) in &source.functions {
+        if name.starts_with("row_single_")
+            && let Some(pixels) = rows.get(name.as_str())
+            && !base.contains(&bits(pixels))
+        {
+            extra.extend(pixels.iter());
+        }
+    }
+
+    let draw = format!("{first_label}_draw");
+    let entry = source.other.iter().any(|(name, _)| *name == draw);
+    let wrappers = source
+        .other
+        .iter()
+        .filter(|(name, _)| *name != draw)
+        .filter_map(|(name, lines)| Some((name.strip_suffix("_draw")?, lines)))
+        .collect::<Vec<_>>();
+    let glyph_entries = wrappers
+        .chunks_exact(2)
+        .map(|pair| (pair[0].0.to_string(), pair[1].0.to_string()))
+        .collect();
+
+    // wrappers with fixed colors load them instead of taking an argument
+    let color = |lines: &[String]| {
+        let (reg, color) = lines.first()?.strip_prefix("li")?.split_once(',')?;
+        if Reg::from_name(reg.trim()) != Some(Reg::A2) {
+            return None;
+        }
+        let color = maybe_hex::<u32>(color.trim()).ok()?;
+        Some(match pixel_format {
+            PixelFormat::Rgba16 => color & 0xFFFF,
+            PixelFormat::Rgba32 => color,
+        })
+    };
+    let glyph_colors = match &wrappers[..] {
+        [(_, single), (_, double), ..] => color(single).zip(color(double)),
+        _ => None,
+    };
+
+    let stride = stride.unwrap_or(pixel_format.default_stride());
+    let quirks = quirks::all()
+        .into_iter()
+        .filter(|q| {
+            let name = q.function();
+            source.functions.iter().any(|(n, body)| {
+                *n == name
+                    && *body
+                        == build_function(
+                            q.row.into(),
+                            glyph_width,
+                            q.double,
+                            pixel_format,
+                            stride,
+                            std::slice::from_ref(q),
+                            registers,
+                        )
+            })
+        })
+        .collect();
+
+    Ok(Recovered {
+        font,
+        extra,
+        quirks,
+        first_label,
+        second_label,
+        glyph_labels,
+        glyph_comments,
+        glyph_width,
+        glyph_height,
+        pixel_format,
+        stride,
+        entry,
+        glyph_entries,
+        glyph_colors,
+        order: source
+            .functions
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect(),
+    })
+}
+
+/// The `--order` file reproducing the extracted function order
+pub fn order_source(functions: &[FunctionInfo]) -> String {
+    let mut rv = String::from("# name address group\n");
+
+    for f in functions {
+        let group = if f.referenced { "table" } else { "extra" };
+        rv += &format!("{} 0x{:08X} {group}\n", f.name, f.address);
+    }
+
+    rv
+}
+
+/// splat `symbol_addrs.txt` lines naming the two table labels and every function, with repeated
+/// names given their address; both labels run to the end of the table, as in the objects
+pub fn symbol_addrs_source(
+    extraction: &Extraction,
+    (first_label, second_label): (&str, &str),
+    registers: Registers,
+) -> String {
+    let Extraction { vram, code, .. } = *extraction;
+    let entry_type = match registers.base {
+        Some(_) => "u16",
+        None => "u32",
+    };
+    let block = (extraction.glyph_height + usize::from(extraction.row_end)) as u32
+        * registers.entry_size() as u32;
+
+    let mut rv = format!(
+        "{first_label} = 0x{vram:08X}; // type:{entry_type} size:0x{:X}\n",
+        code - vram
+    );
+    rv += &format!(
+        "{second_label} = 0x{:08X}; // type:{entry_type} size:0x{:X}\n",
+        vram + block,
+        code - vram - block
+    );
+
+    let names = unique_names(&extraction.functions);
+    for (f, name) in extraction.functions.iter().zip(names) {
+        rv += &format!(
+            "{name} = 0x{:08X}; // type:func size:0x{:X}\n",
+            f.address, f.size
+        );
+    }
+
+    rv
+}
+
+/// The names of `functions`, with repeated names given their address
+fn unique_names(functions: &[FunctionInfo]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    functions
+        .iter()
+        .map(|f| match seen.insert(&f.name) {
+            true => f.name.clone(),
+            false => format!("{}_{:08X}", f.name, f.address),
+        })
+        .collect()
+}
+
+/// The table at the start of `data` and the functions after it as an ELF object with the
+/// symbols `build --emit elf` gives them, for objdiff to compare a build's object with; entries
+/// pointing outside the code are left as they are
+pub fn extraction_object(
+    data: &[u8],
+    extraction: &Extraction,
+    (first_label, second_label): (&str, &str),
+    registers: Registers,
+) -> Result<Vec<u8>> {
+    if registers.base.is_some() {
+        bail!("objects hold tables of addresses, not halfword offsets");
+    }
+    let Extraction { vram, code, .. } = *extraction;
+    let end = extraction
+        .functions
+        .iter()
+        .map(|f| f.address + f.size)
+        .max()
+        .unwrap_or(code);
+    let Some(text) = data.get(..(end - vram) as usize) else {
+        bail!("the input ends before the code at 0x{end:08X}");
+    };
+    let mut text = text.to_vec();
+
+    let mut relocations = vec![];
+    for offset in (0..code - vram).step_by(size_of::<u32>()) {
+        let word = &mut text[offset as usize..][..size_of::<u32>()];
+        let address = BE::read_u32(word);
+        if (code..end).contains(&address) {
+            BE::write_u32(word, address - vram);
+            relocations.push(offset);
+        }
+    }
+
+    let block = (extraction.glyph_height + usize::from(extraction.row_end)) * size_of::<u32>();
+    let table_size = (code - vram) as usize;
+    let label = |name: &str, offset: u32, size: usize, function: bool| elf::Symbol {
+        name: name.to_string(),
+        offset,
+        size: size as u32,
+        function,
+    };
+    // the first and second labels run to the end of the table, as in build_object
+    let mut symbols = vec![
+        label(first_label, 0, table_size, false),
+        label(second_label, block as u32, table_size - block, false),
+    ];
+    let names = unique_names(&extraction.functions);
+    for (f, name) in extraction.functions.iter().zip(names) {
+        symbols.push(label(&name, f.address - vram, f.size as usize, true));
+    }
+
+    Ok(elf::relocatable(&text, &relocations, &symbols))
+}
+
+/// A splat subsegment list for the font at ROM offset `rom`: the table as data and the functions
+/// after it as code, named after `label`
+pub fn splat_source(extraction: &Extraction, rom: u32, label: &str) -> String {
+    let Extraction { vram, code, .. } = *extraction;
+    let end = extraction
+        .functions
+        .iter()
+        .map(|f| f.address + f.size)
+        .max()
+        .unwrap_or(code);
+    let rom_code = rom + (code - vram);
+
+    let mut rv = format!("# {label} at 0x{vram:08X}\n");
+    rv += &format!("- [0x{rom:06X}, data, {label}] # 0x{vram:08X}\n");
+    rv += &format!("- [0x{rom_code:06X}, asm, {label}_rows] # 0x{code:08X}\n");
+    rv += &format!(
+        "# ends at 0x{:06X} (0x{end:08X})\n",
+        rom_code + (end - code)
+    );
+    rv
+}
+
+/// The `--extra-layout` file reproducing the extracted extras and padding
+pub fn extra_layout_source(extra_layout: &[String]) -> String {
+    let mut rv = String::from("# function or .word, from the extra offset on\n");
+
+    for entry in extra_layout {
+        rv += entry;
+        rv += "\n";
+    }
+
+    rv
+}
+
+/// Report of each word that differs between `ours` and `theirs`, both linked at `vram`, decoded
+/// as instructions where possible, and of any difference in length
+pub fn diff_words(ours: &[u8], theirs: &[u8], vram: u32) -> String {
+    let mut rv = String::new();
+
+    let show = |word: u32| match Instr::decode(word) {
+        Some(instr) => format!("{word:08X}  {instr}"),
+        None => format!("{word:08X}  .word 0x{word:08X}"),
+    };
+    let words = |bin: &[u8]| {
+        bin.chunks_exact(size_of::<u32>())
+            .map(BE::read_u32)
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (words(ours), words(theirs));
+
+    let mut differing = 0;
+    for (index, (x, y)) in a.iter().zip(&b).enumerate() {
+        if x == y {
+            continue;
+        }
+        differing += 1;
+        let address = vram.wrapping_add((index * size_of::<u32>()) as u32);
+        rv += &format!("0x{address:08X}:  {:<40}  {}\n", show(*x), show(*y));
+    }
+
+    if ours.len() != theirs.len() {
+        rv += &format!(
+            "length: 0x{:X} bytes, reference 0x{:X}\n",
+            ours.len(),
+            theirs.len()
+        );
+    }
+    rv += &format!("{differing} of {} words differ\n", a.len().min(b.len()));
+
+    rv
+}
+
+/// Report of the differences between two extracted fonts
+pub fn compare(a: &Extraction, b: &Extraction) -> String {
+    let mut rv = String::new();
+
+    let glyphs_a = a
+        .font
+        .chunks_exact(a.glyph_width * a.glyph_height)
+        .collect::<Vec<_>>();
+    let glyphs_b = b
+        .font
+        .chunks_exact(b.glyph_width * b.glyph_height)
+        .collect::<Vec<_>>();
+
+    rv += "layout:\n";
+    rv += &format!("    glyphs: {} -> {}\n", glyphs_a.len(), glyphs_b.len());
+    rv += &format!(
+        "    extra rows: {} -> {}\n",
+        a.extra.len() / a.glyph_width,
+        b.extra.len() / b.glyph_width
+    );
+    if let (Some(x), Some(y)) = (a.stride, b.stride)
+        && x != y
+    {
+        rv += &format!("    stride: {x} -> {y}\n");
+    }
+    if let (Some(x), Some(y)) = (a.addresses.iter().min(), b.addresses.iter().min()) {
+        rv += &format!("    first function: 0x{x:08X} -> 0x{y:08X}\n");
+    }
+    rv += "\n";
+
+    rv += "glyphs:\n";
+    for (index, (x, y)) in glyphs_a.iter().zip(&glyphs_b).enumerate() {
+        if x == y {
+            continue;
+        }
+
+        rv += &format!("    0x{index:02X}:\n");
+        let rows_a = x.chunks_exact(a.glyph_width);
+        for (row_a, row_b) in rows_a.zip(y.chunks_exact(b.glyph_width)) {
+            let marker = if row_a == row_b { "" } else { " *" };
+            rv += &format!(
+                "      {} {}{marker}\n",
+                show_shades(row_a),
+                show_shades(row_b)
+            );
+        }
+    }
+    if glyphs_a.len() != glyphs_b.len() {
+        rv += &format!(
+            "    glyphs 0x{:02X}.. only in one table\n",
+            glyphs_a.len().min(glyphs_b.len())
+        );
+    }
+    rv += "\n";
+
+    let functions = |e: &Extraction| {
+        let mut functions = HashMap::new();
+        for (name, address) in e.table.iter().zip(&e.addresses) {
+            functions.insert(name.clone(), *address);
+        }
+        functions
+    };
+    let functions_a = functions(a);
+    let functions_b = functions(b);
+
+    let mut shared = vec![];
+    let mut only_a = vec![];
+    let mut only_b = vec![];
+    for (name, address) in &functions_a {
+        match functions_b.get(name) {
+            Some(other) => shared.push((name, address, other)),
+            None => only_a.push(name),
+        }
+    }
+    for name in functions_b.keys() {
+        if !functions_a.contains_key(name) {
+            only_b.push(name);
+        }
+    }
+    shared.sort();
+    only_a.sort();
+    only_b.sort();
+
+    rv += &format!("shared row functions: {}\n", shared.len());
+    for (name, x, y) in &shared {
+        if x != y {
+            rv += &format!("    {name}: moved 0x{x:08X} -> 0x{y:08X}\n");
+        }
+    }
+    rv += &format!("only in first: {}\n", only_a.len());
+    for name in only_a {
+        rv += &format!("    {name}: 0x{:08X}\n", functions_a[name]);
+    }
+    rv += &format!("only in second: {}\n", only_b.len());
+    for name in only_b {
+        rv += &format!("    {name}: 0x{:08X}\n", functions_b[name]);
+    }
+
+    rv
+}
+
+/// Address of the table loaded by the first `lui`/`addiu` pair at or after `code`
+pub fn trace_table(data: &[u8], base: u32, code: u32) -> Result<u32> {
+    let Some(start) = code
+        .checked_sub(base)
+        .filter(|&o| (o as usize) < data.len())
+    else {
+        bail!("code address 0x{code:08X} is outside the input file");
+    };
+
+    let mut cursor = Cursor::new(&data[start as usize..]);
+    let mut hi = [None; 32];
+
+    for _ in 0..0x400 {
+        let Ok(instr) = cursor.read_u32::<BE>() else {
+            break;
+        };
+
+        let op = instr >> 26;
+        let rs = ((instr >> 21) & 0x1F) as usize;
+        let rt = ((instr >> 16) & 0x1F) as usize;
+        let rd = ((instr >> 11) & 0x1F) as usize;
+        let imm = instr & 0xFFFF;
+
+        // the low half only completes an address while its source still holds the upper half
+        match (op, hi[rs]) {
+            // lui
+            (0x0F, _) => {
+                hi[rt] = Some(imm << 16);
+                continue;
+            }
+            // addi, addiu
+            (0x08 | 0x09, Some(h)) => return Ok(h.wrapping_add(imm as i16 as u32)),
+            // ori
+            (0x0D, Some(h)) => return Ok(h | imm),
+            _ => {}
+        }
+
+        // anything else written to a register replaces the upper half in it
+        let written = match op {
+            // special, except jr, syscall, break, sync, mthi, mtlo and the multiplies and
+            // divides, which write hi and lo
+            0x00 => match instr & 0x3F {
+                0x08 | 0x0C | 0x0D | 0x0F | 0x11 | 0x13 | 0x18..=0x1F => None,
+                _ => Some(rd),
+            },
+            // regimm's linking branches
+            0x01 if rt & 0x10 != 0 => Some(31),
+            // jal
+            0x03 => Some(31),
+            // immediate arithmetic and logic
+            0x08..=0x0E | 0x18 | 0x19 => Some(rt),
+            // mfc, dmfc and cfc from any coprocessor
+            0x10..=0x12 if rs <= 2 => Some(rt),
+            // loads into general registers
+            0x1A | 0x1B | 0x20..=0x27 | 0x30 | 0x34 | 0x37 => Some(rt),
+            _ => None,
+        };
+        if let Some(r) = written {
+            hi[r] = None;
+        }
+    }
+
+    bail!("no lui/addiu pair found after 0x{code:08X}")
+}
+
+/// `text` drawn with the font in half-block characters, two pixel rows per line, and the
+/// characters that have no glyph, which are left blank
+pub fn render_text(
+    glyphs: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+    charmap: &Charmap,
+    text: &str,
+) -> (String, Vec<char>) {
+    let mut rv = String::new();
+    let mut missing = vec![];
+
+    for line in text.lines() {
+        // an odd last row is paired with a blank one
+        let mut pixels = vec![vec![]; glyph_height.next_multiple_of(2)];
+
+        for c in line.chars() {
+            let glyph = charmap
+                .glyph(c)
+                .and_then(|i| glyphs.chunks_exact(glyph_width * glyph_height).nth(i));
+            if glyph.is_none() && !missing.contains(&c) {
+                missing.push(c);
+            }
+
+            for (y, row) in pixels.iter_mut().enumerate() {
+                match glyph {
+                    Some(g) if y < glyph_height => {
+                        row.extend(&g[y * glyph_width..(y + 1) * glyph_width])
+                    }
+                    _ => row.extend(std::iter::repeat_n(0, glyph_width)),
+                }
+            }
+        }
+
+        for pair in pixels.chunks_exact(2) {
+            for (&top, &bottom) in pair[0].iter().zip(&pair[1]) {
+                rv.push(match (top != 0, bottom != 0) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                });
+            }
+            rv.push('\n');
+        }
+    }
+
+    (rv, missing)
+}
+
+/// Each of the glyphs at `indices` under its index, in half-block characters with two pixel
+/// rows per line, or with `braille` in braille characters of two by four pixels
+pub fn show_glyphs(
+    glyphs: &[u8],
+    indices: &[usize],
+    glyph_width: usize,
+    glyph_height: usize,
+    braille: bool,
+) -> Result<String> {
+    let cell = glyph_width * glyph_height;
+    let (cell_width, cell_height) = if braille { (2, 4) } else { (1, 2) };
+    let mut rv = String::new();
+
+    for (n, &index) in indices.iter().enumerate() {
+        let Some(glyph) = glyphs.chunks_exact(cell).nth(index) else {
+            bail!(
+                "there is no glyph 0x{index:02X}; the font has {}",
+                glyphs.len() / cell
+            );
+        };
+        let set = |x: usize, y: usize| y < glyph_height && glyph[y * glyph_width + x] != 0;
+
+        if n > 0 {
+            rv += "\n";
+        }
+        rv += &format!("0x{index:02X}\n");
+        for y in (0..glyph_height).step_by(cell_height) {
+            for x in (0..glyph_width).step_by(cell_width) {
+                rv.push(match braille {
+                    true => {
+                        // dots 1-3 and 4-6 down the left and right columns, then 7 and 8
+                        let dots = [
+                            (0, 0),
+                            (0, 1),
+                            (0, 2),
+                            (1, 0),
+                            (1, 1),
+                            (1, 2),
+                            (0, 3),
+                            (1, 3),
+                        ];
+                        let bits = dots.iter().enumerate().fold(0, |bits, (bit, &(dx, dy))| {
+                            let on = x + dx < glyph_width && set(x + dx, y + dy);
+                            bits | (on as u32) << bit
+                        });
+                        char::from_u32(0x2800 + bits).unwrap()
+                    }
+                    false => match (set(x, y), set(x, y + 1)) {
+                        (false, false) => ' ',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (true, true) => '█',
+                    },
+                });
+            }
+            rv.push('\n');
+        }
+    }
+
+    Ok(rv)
+}
+
+/// `text` set in the glyphs drawn by the single and then the double blocks, at 1x and then 2x,
+/// with blank rows between the four; returns the pixels, their width and the characters that have
+/// no glyph, which are left blank
+pub fn preview(
+    single: &[u8],
+    double: &[u8],
+    charmap: &Charmap,
+    text: &str,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> (Vec<u8>, u32, Vec<char>) {
+    let cell = glyph_width * glyph_height;
+    let mut missing = vec![];
+    let lines = text
+        .lines()
+        .map(|line| {
+            line.chars()
+                .map(|c| {
+                    let glyph = charmap.glyph(c).filter(|&i| (i + 1) * cell <= single.len());
+                    if glyph.is_none() && !missing.contains(&c) {
+                        missing.push(c);
+                    }
+                    glyph
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let width = lines.iter().map(Vec::len).max().unwrap_or(0) * glyph_width;
+
+    // each line of the text as pixel rows of `width`, scaled up by `scale`
+    let set = |font: &[u8], scale: usize| {
+        let mut rv = vec![];
+        for line in &lines {
+            for y in 0..glyph_height {
+                let mut row = vec![0; width];
+                for (column, glyph) in line.iter().enumerate() {
+                    if let Some(glyph) = glyph {
+                        row[column * glyph_width..][..glyph_width].copy_from_slice(
+                            &font[glyph * cell + y * glyph_width..][..glyph_width],
+                        );
+                    }
+                }
+                let row = row
+                    .iter()
+                    .flat_map(|&p| std::iter::repeat_n(p, scale))
+                    .collect::<Vec<_>>();
+                for _ in 0..scale {
+                    rv.extend(&row);
+                }
+            }
+        }
+        rv
+    };
+
+    let full_width = width * 2;
+    let gap = vec![0; full_width * glyph_height / 2];
+    let mut rv = vec![];
+    for (index, (font, scale)) in [(single, 1), (double, 1), (single, 2), (double, 2)]
+        .into_iter()
+        .enumerate()
+    {
+        if index > 0 {
+            rv.extend(&gap);
+        }
+        for row in set(font, scale).chunks(width * scale) {
+            rv.extend(row);
+            rv.resize(rv.len() + full_width - row.len(), 0);
+        }
+    }
+
+    (rv, full_width as u32, missing)
+}
+
+/// Slots for the characters of `corpus` the charmap doesn't cover: unmapped blank glyphs
+/// first, then new glyphs after the last one
+pub fn localize(
+    glyphs: &[u8],
+    charmap: &Charmap,
+    corpus: &str,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Vec<(usize, char)> {
+    let cell = glyph_width * glyph_height;
+    let num_chars = glyphs.len() / cell;
+    let mut free = (0..num_chars).filter(|&i| {
+        charmap.char(i).is_none() && glyphs[i * cell..(i + 1) * cell].iter().all(|&p| p == 0)
+    });
+    let mut next = num_chars;
+    let mut rv: Vec<(usize, char)> = vec![];
+
+    for c in corpus.chars() {
+        if c.is_control() || charmap.glyph(c).is_some() || rv.iter().any(|&(_, e)| e == c) {
+            continue;
+        }
+
+        let index = free.next().unwrap_or_else(|| {
+            next += 1;
+            next - 1
+        });
+        rv.push((index, c));
+    }
+
+    rv
+}
+
+/// The font with every glyph whose character isn't in `text` blanked, keeping the glyph
+/// indices, and how many glyphs were blanked
+pub fn subset(
+    glyphs: &[u8],
+    charmap: &Charmap,
+    text: &str,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> (Vec<u8>, usize) {
+    let mut rv = glyphs.to_vec();
+    let mut blanked = 0;
+
+    for (index, glyph) in rv.chunks_exact_mut(glyph_width * glyph_height).enumerate() {
+        let used = charmap.char(index).is_some_and(|c| text.contains(c));
+        if !used && glyph.iter().any(|&p| p != 0) {
+            glyph.fill(0);
+            blanked += 1;
+        }
+    }
+
+    (rv, blanked)
+}
+
+/// Glyphs laid out row-major in a grid `width` pixels wide as one glyph-wide column
+pub fn grid_to_strip(data: &[u8], width: u32, glyph_width: usize, glyph_height: usize) -> Vec<u8> {
+    let columns = width as usize / glyph_width;
+    let mut rv = vec![];
+
+    for grid_row in data.chunks_exact(columns * glyph_width * glyph_height) {
+        for column in 0..columns {
+            for line in grid_row.chunks_exact(columns * glyph_width) {
+                rv.extend(&line[column * glyph_width..(column + 1) * glyph_width]);
+            }
+        }
+    }
+
+    rv
+}
+
+/// One column of glyphs laid out in a grid `columns` glyphs wide
+pub fn strip_to_grid(
+    data: &[u8],
+    columns: u32,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Vec<u8> {
+    let columns = columns as usize;
+    let size = glyph_width * glyph_height;
+    let mut rv = vec![];
+
+    for glyphs in data.chunks(size * columns) {
+        let mut grid_row = vec![0; columns * size];
+
+        for (column, glyph) in glyphs.chunks_exact(size).enumerate() {
+            for (y, line) in glyph.chunks_exact(glyph_width).enumerate() {
+                let start = (y * columns + column) * glyph_width;
+                grid_row[start..start + glyph_width].copy_from_slice(line);
+            }
+        }
+
+        rv.extend(grid_row);
+    }
+
+    rv
+}
@@ -1,19 +1,32 @@
-use anyhow::Result;
-use byteorder::{BE, ReadBytesExt};
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_num::maybe_hex;
-use image::EncodableLayout;
-use std::fs::{read, write};
-use std::io::Cursor;
-use std::path::PathBuf;
-
-const SCREEN_WIDTH: i16 = 640;
-type Pixel = u16;
-
-const PROLOGUE: &str = include_str!("prologue.s");
-const EPILOGUE: &str = include_str!("epilogue.s");
-
-const ROW_END: &str = include_str!("row_end.s");
+use gsfont::{
+    BuildOptions, EPILOGUE, ExtraOrder, ExtractOptions, Framing, PROLOGUE, PixelFormat, Registers,
+    Shades, SharedFont, asm, assemble_source, bdf, build, build_binary, build_object,
+    c_header_source, chained_build,
+    charmap::{self, Charmap},
+    compare, diff_words, elf,
+    error::Error,
+    extra_layout_source, extract, extract_chained, extraction_object, glyph_comments, glyph_labels,
+    grid_to_strip, infer_extra_offset, infer_num_chars, json, layout, linker_script_source,
+    localize, manifest,
+    mips::Reg,
+    order_source, parse_extra_layout, parse_extra_order, parse_order, presets, preview, psf,
+    quirks, raw, recover, redundant_extras, render_text, report, rom, roundtrip,
+    rust_module_source, scan, scratch, show_glyphs, splat_source, stats_source, strip_to_grid,
+    symbol_addrs_source,
+    syntax::{Assembler, MacroStyle, Syntax},
+    table_source, timings, trace_table, txt, usage_graph,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{create_dir_all, remove_file, rename, write};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const HEADER: &str = include_str!("header.txt");
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -21,35 +34,248 @@ struct Args {
     #[command(subcommand)]
     command: Command,
 
-    /// Input file
+    /// Input file, or - for stdin
     infile: PathBuf,
 
-    /// Output file
+    /// Output file, or - for stdout
     outfile: PathBuf,
 
-    /// Extra lines path
-    extra: PathBuf,
+    /// Directory that relative output paths are written under
+    #[arg(long, global = true)]
+    out_dir: Option<PathBuf>,
+
+    /// Color that counts as background in input images regardless of its brightness
+    #[arg(long, global = true, value_parser = parse_color)]
+    key_color: Option<[u8; 3]>,
+
+    /// Overwrite output files that already exist
+    #[arg(short, long, global = true)]
+    force: bool,
+
+    /// Check that the output is byte-identical across runs and free of host-specific data, and
+    /// emit the provenance header so it can be audited
+    #[arg(long, global = true)]
+    reproducible: bool,
+
+    /// Emit a provenance header at the top of generated files
+    #[arg(long, global = true)]
+    header: bool,
+
+    /// Template for the provenance header (implies --header)
+    #[arg(long, global = true)]
+    header_template: Option<PathBuf>,
+
+    /// Report how long each phase took
+    #[arg(long, global = true)]
+    timings: bool,
 }
 
 #[derive(Subcommand)]
 enum Command {
     /// Build a font table from an image
-    Build {
+    Build(BuildArgs),
+
+    /// Build every font listed in a TOML manifest, writing the path of each output to the outfile
+    BuildAll {
+        /// Also write a Makefile rule for each output, depending on the manifest and its image
+        #[arg(long)]
+        makefile: Option<PathBuf>,
+    },
+
+    /// Extract a font table to an image
+    Extract {
+        /// VRAM address of the table
+        #[arg(long, value_parser = maybe_hex::<u32>, required_unless_present_any = ["code", "symbol"])]
+        vram: Option<u32>,
+
+        /// Symbol of the table in an ELF infile, which gives its address, the section holding
+        /// the code and, from the symbol's size, the number of characters
+        #[arg(long, conflicts_with_all = ["vram", "code", "base"])]
+        symbol: Option<String>,
+
+        /// VRAM address of a routine that loads the table base with lui/addiu
+        #[arg(long, value_parser = maybe_hex::<u32>, conflicts_with = "vram", requires = "base")]
+        code: Option<u32>,
+
+        /// VRAM address of the start of the input file, or of --rom-offset into it [default: the
+        /// table address]
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        base: Option<u32>,
+
+        /// Offset into the input file that --base, or the table, is loaded from, to extract from a
+        /// whole ROM or firmware image
+        #[arg(long, value_parser = maybe_hex::<usize>, conflicts_with = "symbol")]
+        rom_offset: Option<usize>,
+
+        /// Byte order of the input file [default: the order of its N64 ROM header, or big]
+        #[arg(long, value_enum, conflicts_with = "symbol")]
+        byte_order: Option<rom::ByteOrder>,
+
+        /// Number of characters in the table [default: the symbol's size, or the entries up to
+        /// the lowest address they point at]
+        #[arg(value_parser = maybe_hex::<usize>)]
+        num_chars: Option<usize>,
+
+        /// Offset of duplicate extra data [default: the end of the last function a glyph uses]
+        #[arg(value_parser = maybe_hex::<usize>)]
+        extra_offset: Option<usize>,
+
+        /// First glyph to extract; the table entries of the glyphs before it aren't read
+        #[arg(long, value_parser = maybe_hex::<usize>, default_value_t = 0, conflicts_with_all = ["chained", "also"])]
+        first: usize,
+
+        /// Number of glyphs to extract from --first on [default: the rest of the table]
+        #[arg(long, value_parser = maybe_hex::<usize>, conflicts_with_all = ["chained", "also"])]
+        count: Option<usize>,
+
+        /// Also write the extra lines as an image
+        #[arg(long, conflicts_with = "chained")]
+        extra: Option<PathBuf>,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// The table's blocks are just the glyph's rows, with no row_end entry after them
+        #[arg(long, conflicts_with_all = ["chained", "table", "dir"])]
+        no_row_end: bool,
+
+        /// How the framebuffer stores a pixel
+        #[arg(long, value_enum, default_value_t)]
+        pixel_format: PixelFormat,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+
+        /// Whether table entries are addresses or halfword offsets from the table
+        #[arg(long, value_enum, default_value_t = TableFormat::Word)]
+        table_format: TableFormat,
+
+        /// Register holding the table's address for --table-format half
+        #[arg(long, value_parser = parse_register, default_value = "t0")]
+        base_register: Reg,
+
+        /// Grays to write pixels drawn on their own and as half of a pair in, as <SINGLE>,<PAIR>
+        #[arg(long, value_parser = parse_shades, default_value = "0xFF,0x7F")]
+        shades: Shades,
+
+        /// Lay the glyphs out in a grid this many glyphs wide instead of a one-glyph-wide strip
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        columns: Option<u32>,
+
+        /// The table was built with `build --chained`: one pointer per glyph, no extra lines
+        #[arg(long, conflicts_with_all = [
+            "table", "order", "extra_layout", "metadata", "symbol_addrs", "splat", "object", "html",
+            "svg_dir", "dir", "also",
+        ])]
+        chained: bool,
+
+        /// Also write the pointer table as assembly data
+        #[arg(short, long)]
+        table: Option<PathBuf>,
+
+        /// Also write the address order of all row functions, for `build --order`
+        #[arg(long)]
+        order: Option<PathBuf>,
+
+        /// Also write the functions and padding from the extra offset on, for
+        /// `build --extra-layout`
+        #[arg(long)]
+        extra_layout: Option<PathBuf>,
+
+        /// Also write the glyphs as a JSON document of row bitmasks
+        #[arg(long)]
+        json: Option<PathBuf>,
+
+        /// Also write a JSON report of the table's address and the address, size and users of
+        /// each function
+        #[arg(long)]
+        metadata: Option<PathBuf>,
+
+        /// Also write the table labels and the functions with their addresses and sizes, in the
+        /// format of splat's symbol_addrs.txt
+        #[arg(long)]
+        symbol_addrs: Option<PathBuf>,
+
+        /// Also write splat subsegments splitting the table off as data and the functions as
+        /// code, at ROM offsets from --rom-offset
+        #[arg(long, conflicts_with = "symbol")]
+        splat: Option<PathBuf>,
+
+        /// Also write the table and functions as an ELF object with the symbols of
+        /// `build --emit elf`, the target objdiff compares the build's object with
+        #[arg(long)]
+        object: Option<PathBuf>,
+
+        /// Also write an HTML report of the extracted glyphs
+        #[arg(long)]
+        html: Option<PathBuf>,
+
+        /// Also write the glyph sheet as SVG
+        #[arg(long)]
+        svg: Option<PathBuf>,
+
+        /// Also write the glyphs as a BDF font
+        #[arg(long)]
+        bdf: Option<PathBuf>,
+
+        /// Also write the glyphs as text, a line of . and # per row
+        #[arg(long)]
+        txt: Option<PathBuf>,
+
+        /// Also write the glyphs as a PSF2 font
+        #[arg(long)]
+        psf: Option<PathBuf>,
+
+        /// Write the PSF font as PSF1, padded out to 256 or 512 8-pixel-wide glyphs
+        #[arg(long, requires = "psf")]
+        psf1: bool,
+
+        /// Charmap naming each glyph's character in the JSON and text output, the BDF encodings
+        /// and the PSF Unicode table, and written next to the font with --dir [default: index is
+        /// the code point]
+        #[arg(long)]
+        charmap: Option<PathBuf>,
+
+        /// Also write each glyph as an SVG file in this directory
+        #[arg(long)]
+        svg_dir: Option<PathBuf>,
+
         /// Label for the first part of the table
+        #[arg(long, default_value = "font_single")]
         first_label: String,
 
         /// Label for the second part of the table
+        #[arg(long, default_value = "font_double")]
         second_label: String,
 
-        /// Matching build (using provided extra lines and patches)
-        #[arg(short, long)]
-        matching: bool,
+        /// Write font_<vram>.png, extra_<vram>.png and the table, order, JSON, metadata, symbol,
+        /// object and, without --symbol, splat sidecars for each table into this directory
+        /// instead of the outfile and extra paths
+        #[arg(long, conflicts_with_all = [
+            "extra", "table", "order", "extra_layout", "json", "metadata", "symbol_addrs", "splat",
+            "object", "html", "svg", "svg_dir", "bdf", "psf", "txt",
+        ])]
+        dir: Option<PathBuf>,
+
+        /// Another table to extract, as <VRAM>:<NUM_CHARS>:<EXTRA_OFFSET>
+        #[arg(long, requires = "dir", value_parser = parse_table_spec)]
+        also: Vec<(u32, usize, usize)>,
     },
 
-    /// Extract a font table to an image
-    Extract {
+    /// Compare the font tables in two binaries
+    Compare {
+        /// Second binary
+        other: PathBuf,
+
         /// VRAM address of the table
-        #[arg(value_parser = maybe_hex::<u32>)]
+        #[arg(long, value_parser = maybe_hex::<u32>)]
         vram: u32,
 
         /// Number of characters in the table
@@ -59,321 +285,2647 @@ enum Command {
         /// Offset of duplicate extra data
         #[arg(value_parser = maybe_hex::<usize>)]
         extra_offset: usize,
+
+        /// VRAM address of the second table [default: same as the first]
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        other_vram: Option<u32>,
+
+        /// Number of characters in the second table [default: same as the first]
+        #[arg(long, value_parser = maybe_hex::<usize>)]
+        other_num_chars: Option<usize>,
+
+        /// Offset of duplicate extra data in the second table [default: same as the first]
+        #[arg(long, value_parser = maybe_hex::<usize>)]
+        other_extra_offset: Option<usize>,
+
+        /// Pixels per glyph row in both tables
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph in both tables
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// How the framebuffer of both tables stores a pixel
+        #[arg(long, value_enum, default_value_t)]
+        pixel_format: PixelFormat,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function in both
+        /// tables, as <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+    },
+
+    /// Look for font tables in a binary and write each as <VRAM>:<NUM_CHARS>:<EXTRA_OFFSET>, the
+    /// format of `extract --also`; the extra offset is left off when the code doesn't follow the
+    /// table
+    Scan {
+        /// VRAM address of the start of the input file, or of --rom-offset into it
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        base: u32,
+
+        /// Offset into the input file that --base is loaded from
+        #[arg(long, value_parser = maybe_hex::<usize>)]
+        rom_offset: Option<usize>,
+
+        /// Byte order of the input file [default: the order of its N64 ROM header, or big]
+        #[arg(long, value_enum)]
+        byte_order: Option<rom::ByteOrder>,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// How the framebuffer stores a pixel
+        #[arg(long, value_enum, default_value_t)]
+        pixel_format: PixelFormat,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+    },
+
+    /// Compare a generated `.s` file, assembled at --vram, or an assembled table word by word
+    /// with the same region of a reference binary, writing each mismatch
+    Diff {
+        /// Reference binary, such as the original firmware
+        reference: PathBuf,
+
+        /// VRAM address the table is linked at
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        vram: u32,
+
+        /// VRAM address of the start of the reference [default: the table address]
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        base: Option<u32>,
+    },
+
+    /// Write one row function of a binary as a decomp.me scratch, its machine code the target
+    /// and its registers and pixels the context, as JSON for the scratch API
+    ExportScratch {
+        /// VRAM address of the function
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        address: u32,
+
+        /// VRAM address of the start of the input file, or of --rom-offset into it [default: the
+        /// function address]
+        #[arg(long, value_parser = maybe_hex::<u32>)]
+        base: Option<u32>,
+
+        /// Offset into the input file that --base, or the function, is loaded from
+        #[arg(long, value_parser = maybe_hex::<usize>)]
+        rom_offset: Option<usize>,
+
+        /// Byte order of the input file [default: the order of its N64 ROM header, or big]
+        #[arg(long, value_enum)]
+        byte_order: Option<rom::ByteOrder>,
+
+        /// Name of the scratch and function [default: the name of the row it draws]
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// How the framebuffer stores a pixel
+        #[arg(long, value_enum, default_value_t)]
+        pixel_format: PixelFormat,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+
+        /// Whether table entries are addresses or halfword offsets from the table
+        #[arg(long, value_enum, default_value_t = TableFormat::Word)]
+        table_format: TableFormat,
+
+        /// Register holding the table's address for --table-format half
+        #[arg(long, value_parser = parse_register, default_value = "t0")]
+        base_register: Reg,
+
+        /// decomp.me compiler of the scratch
+        #[arg(long, default_value = "ido7.1")]
+        compiler: String,
+
+        /// Flags of the scratch's compiler
+        #[arg(long, default_value = "-O2 -mips2")]
+        compiler_flags: String,
+    },
+
+    /// Render a string using the glyphs of a font image in half blocks, for the terminal
+    Print {
+        /// Text to render
+        text: String,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// Charmap mapping glyph indices to characters [default: index is the code point]
+        #[arg(long)]
+        charmap: Option<PathBuf>,
+    },
+
+    /// Draw glyphs of a font image as text, for the terminal
+    Show {
+        /// Indices of the glyphs to draw [default: all of them]
+        #[arg(value_parser = maybe_hex::<usize>)]
+        glyphs: Vec<usize>,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// Draw in braille, two by four pixels per character, instead of half blocks
+        #[arg(long)]
+        braille: bool,
+    },
+
+    /// Render a string to a PNG the way the generated code draws it, with the single and double
+    /// blocks at 1x and 2x; the infile is a font image or a generated or extracted `.s` table,
+    /// which is drawn with its own patches, stride and pixel format
+    Preview {
+        /// Text to render
+        text: String,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32 [default: 8, or the table's]
+        #[arg(long, value_parser = parse_glyph_width)]
+        glyph_width: Option<usize>,
+
+        /// Rows per glyph [default: 8, or the table's]
+        #[arg(long, value_parser = parse_glyph_height)]
+        glyph_height: Option<usize>,
+
+        /// Charmap mapping glyph indices to characters [default: index is the code point]
+        #[arg(long)]
+        charmap: Option<PathBuf>,
+
+        /// Apply this known hand patch (repeatable) [default: the table's]
+        #[arg(long)]
+        quirk: Vec<String>,
+
+        /// Apply the hand patches of this firmware preset
+        #[arg(long, conflicts_with = "quirk")]
+        preset: Option<String>,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+    },
+
+    /// Give every character of a translation a glyph, keeping the existing glyph indices and
+    /// writing the font table to the outfile
+    Localize {
+        /// Text whose characters the font has to cover
+        corpus: PathBuf,
+
+        /// Label for the first part of the table
+        first_label: String,
+
+        /// Label for the second part of the table
+        second_label: String,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// Charmap of the font [default: index is the code point]
+        #[arg(long)]
+        charmap: Option<PathBuf>,
+
+        /// Font image, with glyphs of the same size, to copy the new characters' glyphs from
+        /// [default: leave them blank]
+        #[arg(long)]
+        source: Option<PathBuf>,
+
+        /// Charmap of the --source font [default: index is the code point]
+        #[arg(long, requires = "source")]
+        source_charmap: Option<PathBuf>,
+
+        /// Write the font image with the new glyphs here
+        #[arg(long)]
+        image: PathBuf,
+
+        /// Write the charmap covering the new glyphs here
+        #[arg(long)]
+        write_charmap: PathBuf,
+    },
+
+    /// Re-emit a generated assembly file in the current output format
+    Fmt {
+        /// Keep the functions in their existing order instead of the default one
+        #[arg(long)]
+        keep_order: bool,
+
+        /// Write registers by number, as $16, instead of by name
+        #[arg(long)]
+        numeric_registers: bool,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+    },
+
+    /// Recover the font image and extra lines from a generated assembly file
+    Recover {
+        /// Also write the extra lines as an image
+        #[arg(long)]
+        extra: Option<PathBuf>,
+
+        /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+        /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+        #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+        registers: Registers,
+    },
+
+    /// Reflow a font image between the one-glyph-wide strip and a grid of glyphs
+    Convert {
+        /// Number of glyph columns in the output (omit to produce a strip)
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        columns: Option<u32>,
+
+        /// Pixels per glyph row: 8, 16, 24 or 32
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+        glyph_width: usize,
+
+        /// Rows per glyph
+        #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+        glyph_height: usize,
+
+        /// Number of glyphs to keep (drops grid padding when converting back to a strip)
+        #[arg(short, long)]
+        num_chars: Option<usize>,
     },
 }
 
-fn build_function(row: u8, double: bool, matching: bool) -> String {
-    let mut rv = String::new();
+/// Options of `build`, which the fonts of a `build-all` manifest also take
+#[derive(clap::Args)]
+struct BuildArgs {
+    /// Label for the first part of the table
+    first_label: String,
 
-    rv += "    lw     s0, 0(a0)\n";
-    rv += &format!("    addi   a0, a0, {}\n", size_of::<u32>());
-
-    for i in (0..u8::BITS).step_by(2) {
-        let pair = (row >> (u8::BITS - i - 2)) & 0b00000011;
-        if double {
-            match pair {
-                0b00 => {}
-                0b01 => {
-                    rv += &format!(
-                        "    sh     s1, {}(a1)\n",
-                        (i + 1) * size_of::<Pixel>() as u32
-                    );
-                }
-                0b10 => {
-                    rv += &format!("    sh     s1, {}(a1)\n", i * size_of::<Pixel>() as u32);
-                }
-                0b11 => {
-                    rv += &format!("    sw     s1, {}(a1)\n", i * size_of::<Pixel>() as u32);
-                }
-                _ => unreachable!(),
-            }
-        } else {
-            match pair {
-                0b00 => {}
-                0b01 => {
-                    rv += &format!(
-                        "    sh     s1, {}(a1)\n",
-                        (i + 1) * size_of::<Pixel>() as u32
-                    );
-                }
-                0b10 => {
-                    rv += &format!("    sh     s1, {}(a1)\n", i * size_of::<Pixel>() as u32);
-                }
-                0b11 => {
-                    if matching && row == 0b11011000 && i == 0 {
-                        // SURELY this must have been a manual patch
-                        rv += &format!("    sw     s1, {}(a1)\n", i * size_of::<Pixel>() as u32);
-                    } else {
-                        rv += &format!("    sh     s1, {}(a1)\n", i * size_of::<Pixel>() as u32);
-                    }
-                    rv += &format!(
-                        "    sh     s1, {}(a1)\n",
-                        (i + 1) * size_of::<Pixel>() as u32
-                    );
-                }
-                _ => unreachable!(),
-            }
-        }
+    /// Label for the second part of the table
+    second_label: String,
+
+    /// Matching build (using the firmware's extra lines and patches)
+    #[arg(short, long)]
+    matching: bool,
+
+    /// Don't apply any of the known firmware hand patches, even with --matching
+    #[arg(long, conflicts_with = "quirk")]
+    no_quirks: bool,
+
+    /// Apply only this known hand patch (repeatable) [default: all of them when matching]
+    #[arg(long)]
+    quirk: Vec<String>,
+
+    /// Firmware layout preset fixing the stride and patches
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Read the hand patches and function orders of each firmware from this file instead of
+    /// the bundled ones, in the same format as src/quirks.txt
+    #[arg(long)]
+    patches: Option<PathBuf>,
+
+    /// Original binary whose table at --vram the build should match; where its row functions
+    /// differ from the generated ones by hand patches, the patches are derived and applied
+    #[arg(long, requires = "vram")]
+    target: Option<PathBuf>,
+
+    /// VRAM address of the start of --target [default: the table address]
+    #[arg(long, value_parser = maybe_hex::<u32>, requires = "target")]
+    target_base: Option<u32>,
+
+    /// Also write the patches derived from --target, in the format of --patches
+    #[arg(long, requires = "target")]
+    write_patches: Option<PathBuf>,
+
+    /// Pixels per glyph row: 8, 16, 24 or 32
+    #[arg(long, default_value_t = 8, value_parser = parse_glyph_width)]
+    glyph_width: usize,
+
+    /// Rows per glyph
+    #[arg(long, default_value_t = 8, value_parser = parse_glyph_height)]
+    glyph_height: usize,
+
+    /// Framebuffer width in pixels, which each row function advances by [default: 640, or the
+    /// preset's]
+    #[arg(long, value_parser = parse_fb_width)]
+    fb_width: Option<i16>,
+
+    /// How the framebuffer stores a pixel
+    #[arg(long, value_enum, default_value_t)]
+    pixel_format: PixelFormat,
+
+    /// Registers holding the table pointer, framebuffer pointer, color and next function, as
+    /// <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>
+    #[arg(long, value_parser = parse_registers, default_value = "a0,a1,s1,s0")]
+    registers: Registers,
+
+    /// Whether table entries are addresses or halfword offsets from the table
+    #[arg(long, value_enum, default_value_t = TableFormat::Word)]
+    table_format: TableFormat,
+
+    /// Register holding the table's address for --table-format half
+    #[arg(long, value_parser = parse_register, default_value = "t0")]
+    base_register: Reg,
+
+    /// Write registers by number, as $16, instead of by name
+    #[arg(long)]
+    numeric_registers: bool,
+
+    /// Read the font as a raw dump of packed glyph rows instead of an image
+    #[arg(long)]
+    raw: bool,
+
+    /// Bit holding the leftmost pixel of each raw byte
+    #[arg(long, value_enum, default_value_t = raw::BitOrder::Msb, requires = "raw")]
+    bit_order: raw::BitOrder,
+
+    /// Bitplanes per raw row, such as 2 for Game Boy tiles
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8), requires = "raw")]
+    planes: u8,
+
+    /// Whether raw bitplanes alternate per row or follow each other per glyph
+    #[arg(long, value_enum, default_value_t = raw::PlaneLayout::Interleaved, requires = "raw")]
+    plane_layout: raw::PlaneLayout,
+
+    /// Pad the table with blank glyphs (or truncate it, dropping the padding of a grid) to
+    /// exactly this many characters
+    #[arg(short, long, value_parser = maybe_hex::<usize>)]
+    num_chars: Option<usize>,
+
+    /// Extra lines image (repeatable), whose rows get functions after the glyphs' in order
+    #[arg(long = "extra")]
+    extras: Vec<PathBuf>,
+
+    /// File listing additional extra lines images, one path per line
+    #[arg(long)]
+    extra_list: Option<PathBuf>,
+
+    /// Another font whose tables use the same row functions, as
+    /// <IMAGE>:<FIRST_LABEL>:<SECOND_LABEL>; its tables follow this font's
+    #[arg(long, value_parser = parse_share_spec)]
+    share: Vec<(PathBuf, String, String)>,
+
+    /// Emit one function per glyph with the rows chained by jumps instead of a table of
+    /// row function pointers; the tables then hold one pointer per glyph
+    #[arg(long, conflicts_with_all = [
+        "incbin", "verify", "order", "entry", "glyph_entries", "glyph_labels", "linker_script",
+        "stats", "share", "extra_layout",
+    ])]
+    chained: bool,
+
+    /// Emit a C-callable <first_label>_draw(block, dst, color) that returns with jr ra
+    #[arg(long)]
+    entry: bool,
+
+    /// Also emit a C-callable wrapper per glyph block, named after its character
+    #[arg(long)]
+    glyph_entries: bool,
+
+    /// Pixel values the glyph wrappers draw single and double blocks in, as <SINGLE>,<PAIR>,
+    /// instead of taking a color argument
+    #[arg(long, requires = "glyph_entries", value_parser = parse_colors)]
+    colors: Option<(u32, u32)>,
+
+    /// Write the row functions to a binary file referenced with .incbin
+    #[arg(long)]
+    incbin: Option<PathBuf>,
+
+    /// Label each glyph's table entries, named after its character
+    #[arg(long)]
+    glyph_labels: bool,
+
+    /// Charmap mapping glyph indices to characters [default: the PSF infile's Unicode table,
+    /// or index is the code point]
+    #[arg(long)]
+    charmap: Option<PathBuf>,
+
+    /// Blank every glyph whose character, by the charmap, isn't in this text file
+    #[arg(long)]
+    subset: Option<PathBuf>,
+
+    /// Emit the functions in the order listed in this file
+    #[arg(long)]
+    order: Option<PathBuf>,
+
+    /// Order of the functions for rows only the glyphs use, which follow the extra lines'
+    #[arg(long, value_enum, default_value_t = ExtraRowOrder::FirstSeen)]
+    extra_order: ExtraRowOrder,
+
+    /// File listing rows for --extra-order from-file, one per line as in the function names
+    #[arg(long, required_if_eq("extra_order", "from-file"))]
+    extra_order_file: Option<PathBuf>,
+
+    /// End the code with the functions and padding listed in this file, as written by
+    /// `extract --extra-layout`
+    #[arg(long)]
+    extra_layout: Option<PathBuf>,
+
+    /// Assembler to write the source for; armips and bass output starts at --vram
+    #[arg(long, value_enum, default_value_t)]
+    assembler: Assembler,
+
+    /// Macros the GNU as output defines its functions and labels with
+    #[arg(long, value_enum, default_value_t)]
+    macro_style: MacroStyle,
+
+    /// Mark functions with .type, .ent, .end and .size so objdump, gdb and asm-differ see
+    /// them as functions of the right size
+    #[arg(long)]
+    function_directives: bool,
+
+    /// Start the assembly with this file instead of the built-in includes and directives
+    #[arg(long)]
+    prologue: Option<PathBuf>,
+
+    /// End the assembly with this file instead of the built-in directives
+    #[arg(long)]
+    epilogue: Option<PathBuf>,
+
+    /// Source of the row_end function, in place of the built-in one; binary output keeps the
+    /// built-in code
+    #[arg(long)]
+    row_end: Option<PathBuf>,
+
+    /// Check that drawing and extracting the generated code reproduces every glyph
+    #[arg(long)]
+    verify: bool,
+
+    /// Write a Graphviz graph of the row functions each glyph uses
+    #[arg(long)]
+    dot: Option<PathBuf>,
+
+    /// Write a CSV of the instruction, store and pixel counts of each function and glyph
+    #[arg(long)]
+    stats: Option<PathBuf>,
+
+    /// Write a C header declaring the exported labels and the table dimensions
+    #[arg(long)]
+    c_header: Option<PathBuf>,
+
+    /// Write a GNU ld linker script fragment placing the table at --vram
+    #[arg(long, requires = "vram")]
+    linker_script: Option<PathBuf>,
+
+    /// VRAM address of the table
+    #[arg(long, value_parser = maybe_hex::<u32>, required_if_eq("emit", "bin"))]
+    vram: Option<u32>,
+
+    /// Object file the linker script places [default: outfile with a .o extension]
+    #[arg(long)]
+    object: Option<PathBuf>,
+
+    /// What to write to the outfile
+    #[arg(long, value_enum, default_value_t = Emit::Asm)]
+    emit: Emit,
+}
+
+impl BuildArgs {
+    /// The files other than the infile that the build reads, except the images an --extra-list
+    /// names
+    fn inputs(&self) -> Vec<&Path> {
+        let mut rv = self
+            .share
+            .iter()
+            .map(|(path, ..)| path.as_path())
+            .collect::<Vec<_>>();
+        rv.extend(self.extras.iter().map(PathBuf::as_path));
+        rv.extend(
+            [
+                &self.extra_list,
+                &self.patches,
+                &self.target,
+                &self.charmap,
+                &self.subset,
+                &self.order,
+                &self.extra_order_file,
+                &self.extra_layout,
+                &self.prologue,
+                &self.epilogue,
+                &self.row_end,
+            ]
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path),
+        );
+        rv
     }
+}
 
-    rv += "    jr     s0\n";
-    rv += &format!(
-        "     addi  a1, a1, {}\n",
-        SCREEN_WIDTH * size_of::<Pixel>() as i16
-    );
+/// The options of one font of a manifest, which are given without a subcommand
+#[derive(Parser)]
+#[command(name = "build", no_binary_name = true)]
+struct FontArgs {
+    #[command(flatten)]
+    build: BuildArgs,
+}
 
-    rv
+fn parse_glyph_height(s: &str) -> Result<usize, String> {
+    match maybe_hex(s)? {
+        0 => Err("glyphs need at least one row".to_string()),
+        n => Ok(n),
+    }
 }
 
-fn build(
-    data: &[u8],
-    first_label: &str,
-    second_label: &str,
-    extra: Option<&[u8]>,
-) -> Result<String> {
-    let mut rv = String::from(PROLOGUE);
+fn parse_glyph_width(s: &str) -> Result<usize, String> {
+    match maybe_hex(s)? {
+        n @ (8 | 16 | 24 | 32) => Ok(n),
+        _ => Err("glyph rows must be 8, 16, 24 or 32 pixels wide".to_string()),
+    }
+}
 
-    let mut char_rows = vec![];
+fn parse_fb_width(s: &str) -> Result<i16, String> {
+    match maybe_hex::<u16>(s)? {
+        n @ 1.. if n <= i16::MAX as u16 => Ok(n as i16),
+        _ => Err(format!(
+            "the framebuffer must be 1 to {} pixels wide",
+            i16::MAX
+        )),
+    }
+}
 
-    for ch in data.chunks_exact(8 * 8) {
-        let mut buf = [0; 8];
-        for (index, row) in ch.chunks_exact(8).enumerate() {
-            let mut b = 0;
+fn parse_colors(s: &str) -> Result<(u32, u32), String> {
+    let Some((single, pair)) = s.split_once(',') else {
+        return Err("expected <SINGLE>,<PAIR>".to_string());
+    };
+    Ok((maybe_hex(single.trim())?, maybe_hex(pair.trim())?))
+}
 
-            for i in row {
-                b = (b << 1) | (*i != 0) as u8;
-            }
+fn parse_shades(s: &str) -> Result<Shades, String> {
+    let Some((single, pair)) = s.split_once(',') else {
+        return Err("expected <SINGLE>,<PAIR>".to_string());
+    };
+    Ok(Shades {
+        single: maybe_hex(single.trim())?,
+        pair: maybe_hex(pair.trim())?,
+    })
+}
 
-            buf[index] = b;
-        }
+fn parse_registers(s: &str) -> Result<Registers, String> {
+    let regs = s
+        .split(',')
+        .map(|name| Reg::from_name(name.trim()).ok_or(format!("{name:?} is not a register")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [list, framebuffer, color, next] = regs[..] else {
+        return Err("expected <LIST>,<FRAMEBUFFER>,<COLOR>,<NEXT>".to_string());
+    };
+    let registers = Registers {
+        list,
+        framebuffer,
+        color,
+        next,
+        base: None,
+    };
+    registers.check(false).map_err(|e| e.to_string())?;
+    Ok(registers)
+}
 
-        char_rows.push(buf);
-    }
+fn parse_register(s: &str) -> Result<Reg, String> {
+    Reg::from_name(s.trim()).ok_or(format!("{s:?} is not a register"))
+}
+
+fn parse_share_spec(s: &str) -> Result<(PathBuf, String, String), String> {
+    let [second_label, first_label, path] = s.rsplitn(3, ':').collect::<Vec<_>>()[..] else {
+        return Err("expected <IMAGE>:<FIRST_LABEL>:<SECOND_LABEL>".to_string());
+    };
+
+    Ok((
+        path.into(),
+        first_label.to_string(),
+        second_label.to_string(),
+    ))
+}
+
+fn parse_table_spec(s: &str) -> Result<(u32, usize, usize), String> {
+    let [vram, num_chars, extra_offset] = s.split(':').collect::<Vec<_>>()[..] else {
+        return Err("expected <VRAM>:<NUM_CHARS>:<EXTRA_OFFSET>".to_string());
+    };
+
+    Ok((
+        maybe_hex(vram)?,
+        maybe_hex(num_chars)?,
+        maybe_hex(extra_offset)?,
+    ))
+}
 
-    let mut rows = vec![];
+fn check_dimensions(
+    path: &Path,
+    width: u32,
+    height: u32,
+    strip: Option<u32>,
+    glyph_width: u32,
+    glyph_height: u32,
+) -> Result<()> {
+    let (w, h) = (glyph_width, glyph_height);
+    let error = |problem: String| Error::BadDimensions {
+        path: path.to_path_buf(),
+        width,
+        height,
+        problem,
+    };
+
+    match strip {
+        Some(w) if width != w => bail!(error(format!(
+            "it must be exactly {w} pixels wide (one glyph per {h} rows)"
+        ))),
+        None if width < w => bail!(error(format!(
+            "it must be at least one {w}-pixel glyph wide"
+        ))),
+        None if !width.is_multiple_of(w) => bail!(error(format!(
+            "its width must be a multiple of {w}; the nearest valid widths are {} and {}",
+            width / w * w,
+            (width / w + 1) * w
+        ))),
+        _ => {}
+    }
 
-    for i in 0..(1 << 7) {
-        rows.push(((i << 3) & 0b11111000) | ((i >> 4) & 0b00000110));
+    if !height.is_multiple_of(h) {
+        let columns = (width / w) as usize;
+        let complete = (height / h) as usize * columns;
+        bail!(error(format!(
+            "its height isn't a multiple of {h}: that's {complete} complete glyphs with {} rows \
+             ({} pixels) left over; use a height of {} or {}",
+            height % h,
+            (height % h) * width,
+            height / h * h,
+            (height / h + 1) * h
+        )));
     }
 
-    let mut extra_rows = vec![];
+    Ok(())
+}
 
-    if let Some(e) = extra {
-        for row in e.chunks_exact(8) {
-            let mut b = 0;
+/// Whether the path is `-`, standing for stdin or stdout
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
 
-            for i in row {
-                b = (b << 1) | (*i != 0) as u8;
-            }
+/// The contents of a file, or of stdin for `-`, which is only read once however many times it's
+/// asked for
+fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+    static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
 
-            extra_rows.push(b);
-        }
+    if !is_stdio(path.as_ref()) {
+        return std::fs::read(path);
     }
+    if let Some(data) = STDIN.get() {
+        return Ok(data.clone());
+    }
+    let mut data = vec![];
+    std::io::stdin().lock().read_to_end(&mut data)?;
+    Ok(STDIN.get_or_init(|| data).clone())
+}
 
-    for ch in &char_rows {
-        for i in ch {
-            if !rows.contains(i) && !extra_rows.contains(i) {
-                extra_rows.push(*i);
-            }
-        }
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
+
+fn is_bdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("bdf"))
+}
+
+fn is_psf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("psf"))
+}
+
+fn is_txt(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "txt")
+}
+
+/// The images an --extra-list file names, one per line relative to it, skipping blank lines and
+/// `#` comments
+fn read_extra_list(list: &Path) -> Result<Vec<PathBuf>> {
+    let dir = list.parent().unwrap_or(Path::new(""));
+    Ok(String::from_utf8(read(list)?)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| dir.join(line))
+        .collect())
+}
+
+fn is_asm(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "s")
+}
+
+/// Decode 1-bit grayscale and two-color paletted PNGs straight from their bit data, so they
+/// never go through a colour conversion; anything else is left to the `image` crate
+fn open_bilevel(path: &Path, key: Option<[u8; 3]>) -> Result<Option<(Vec<u8>, u32, u32)>> {
+    if !is_stdio(path)
+        && !path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+    {
+        return Ok(None);
     }
 
-    for (index, row) in char_rows.iter().enumerate() {
-        if index == 0 {
-            rv += &format!("EXPORT({})\n", first_label);
+    let data = read(path)?;
+    if !data.starts_with(b"\x89PNG") {
+        return Ok(None);
+    }
+    let decoder = png::Decoder::new(Cursor::new(data));
+    let mut reader = decoder.read_info()?;
+    let info = reader.info();
+
+    let colors: Vec<[u8; 3]> = match (info.color_type, info.bit_depth) {
+        (png::ColorType::Grayscale, png::BitDepth::One) => vec![[0; 3], [0xFF; 3]],
+        (png::ColorType::Indexed, _) => match &info.palette {
+            Some(palette) if palette.len() <= 2 * 3 => palette
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect(),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    // same weights as image's to_luma8
+    let luma = colors
+        .iter()
+        .map(|&c| match key {
+            Some(key) if c == key => 0,
+            _ => ((2126 * c[0] as u32 + 7152 * c[1] as u32 + 722 * c[2] as u32) / 10000) as u8,
+        })
+        .collect::<Vec<_>>();
+
+    let bits = info.bit_depth as usize;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    let (width, height) = (frame.width, frame.height);
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for line in buf[..frame.buffer_size()].chunks_exact(frame.line_size) {
+        for x in 0..width as usize {
+            let bit = x * bits;
+            let index = (line[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+            let Some(&p) = luma.get(index as usize) else {
+                bail!(
+                    "{} uses palette entry {index}, which doesn't exist",
+                    path.display()
+                );
+            };
+            pixels.push(p);
         }
+    }
 
-        for i in row {
-            rv += &format!("    .word row_single_{i:08b}\n");
-        }
+    Ok(Some((pixels, width, height)))
+}
 
-        rv += "    .word row_end\n\n";
+/// Luma pixels of an image, with pixels of the key color (if any) cleared to background
+/// Open a font image, which is either exactly `strip` pixels wide or, without it, a grid of
+/// `glyph_width`-pixel glyphs
+fn open_image(
+    path: &Path,
+    strip: Option<u32>,
+    (glyph_width, glyph_height): (u32, u32),
+    key: Option<[u8; 3]>,
+) -> Result<(Vec<u8>, u32)> {
+    let check =
+        |width, height| check_dimensions(path, width, height, strip, glyph_width, glyph_height);
+
+    if let Some((pixels, width, height)) = open_bilevel(path, key)? {
+        check(width, height)?;
+        return Ok((pixels, width));
+    }
 
-        if index == 0 {
-            rv += &format!("EXPORT({})\n", second_label);
+    let image = match is_stdio(path) {
+        true => image::load_from_memory(&read(path)?)?,
+        false => image::open(path)?,
+    };
+    check(image.width(), image.height())?;
+
+    let mut pixels = image.to_luma8().into_raw();
+    if let Some(key) = key {
+        for (p, rgb) in pixels.iter_mut().zip(image.to_rgb8().pixels()) {
+            if rgb.0 == key {
+                *p = 0;
+            }
         }
+    }
 
-        for i in row {
-            rv += &format!("    .word row_double_{i:08b}\n");
-        }
+    Ok((pixels, image.width()))
+}
 
-        rv += "    .word row_end\n\n";
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]),
+        _ => Err("expected a color as RRGGBB or #RRGGBB".to_string()),
     }
+}
 
-    for &i in &rows {
-        let name = format!("row_single_{i:08b}");
-        rv += &format!("LEAF({name})\n");
-        rv += &build_function(i, false, extra.is_some());
-        rv += &format!("END({name})\n\n");
+fn load_glyphs(
+    path: &Path,
+    glyph_width: usize,
+    glyph_height: usize,
+    key: Option<[u8; 3]>,
+) -> Result<Vec<u8>> {
+    if is_json(path) {
+        let text = String::from_utf8(read(path)?)?;
+        return Ok(json::from_json(&text, glyph_width, glyph_height)?.0);
+    }
+    if is_txt(path) {
+        return txt::from_txt(&String::from_utf8(read(path)?)?, glyph_width, glyph_height);
     }
 
-    rv += ROW_END;
+    let cell = (glyph_width as u32, glyph_height as u32);
+    let (pixels, width) = open_image(path, None, cell, key)?;
+    Ok(grid_to_strip(&pixels, width, glyph_width, glyph_height))
+}
 
-    for &i in &rows {
-        let name = format!("row_double_{i:08b}");
-        rv += &format!("LEAF({name})\n");
-        rv += &build_function(i, true, extra.is_some());
-        rv += &format!("END({name})\n\n");
+fn check_reproducible(out: &[u8], again: &[u8], inputs: &[&Path]) -> Result<()> {
+    if out != again {
+        bail!("output differs between two runs over the same input");
     }
 
-    for &i in &extra_rows {
-        let name = format!("row_double_{i:08b}");
-        rv += &format!("LEAF({name})\n");
-        rv += &build_function(i, true, extra.is_some());
-        rv += &format!("END({name})\n\n");
+    if out.contains(&b'\r') {
+        bail!("output contains platform-specific line endings");
     }
 
-    for &i in &extra_rows {
-        let name = format!("row_single_{i:08b}");
-        rv += &format!("LEAF({name})\n");
-        rv += &build_function(i, false, extra.is_some());
-        rv += &format!("END({name})\n\n");
+    let mut host_paths = vec![std::env::current_dir()?];
+    for input in inputs.iter().filter(|input| !is_stdio(input)) {
+        host_paths.push(input.canonicalize()?);
     }
 
-    rv += EPILOGUE;
+    for path in host_paths {
+        let path = path.to_string_lossy();
+        if path.len() > 1 && out.windows(path.len()).any(|w| w == path.as_bytes()) {
+            bail!("output contains the host path {path}");
+        }
+    }
 
-    Ok(rv)
+    Ok(())
 }
 
-fn parse_function<T>(cursor: &mut Cursor<T>) -> Result<Option<Box<[u8]>>>
-where
-    Cursor<T>: ReadBytesExt,
-{
-    let prologue = (cursor.read_u32::<BE>()?, cursor.read_u32::<BE>()?);
-    match prologue {
-        (/* lw $s0, 0($a0) */ 0x8C900000, /* addi $a0, $a0, 4 */ 0x20840004) => {
-            let mut pixels = vec![0, 0, 0, 0, 0, 0, 0, 0];
-
-            let mut instr;
-            while {
-                instr = cursor.read_u32::<BE>()?;
-                instr != /* jr $s0 */ 0x02000008
-            } {
-                let w = instr & 0xFC000000 == 0xAC000000;
-                let offset = instr & 0x0000FFFF;
-                if w {
-                    pixels[(offset >> 1) as usize] = 0x7F;
-                    pixels[((offset >> 1) + 1) as usize] = 0x7F;
-                } else {
-                    pixels[(offset >> 1) as usize] = 0xFF;
-                }
-            }
-            // consume epilogue
-            cursor.read_u32::<BE>()?;
-            Ok(Some(pixels.into_boxed_slice()))
-        }
-        (/* lw $s1, 0($sp) */ 0x8FB10000, /* addi $sp, $sp, 4 */ 0x23BD0004) => {
-            // consume epilogue
-            cursor.read_u32::<BE>()?;
-            cursor.read_u32::<BE>()?;
-            cursor.read_u32::<BE>()?;
-            cursor.read_u32::<BE>()?;
-            Ok(None)
+fn header(template: &str, inputs: &[&Path]) -> Result<String> {
+    let command = std::iter::once(env!("CARGO_PKG_NAME").to_string())
+        .chain(
+            std::env::args_os()
+                .skip(1)
+                .map(|a| a.to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut rv = String::new();
+
+    for line in template.lines() {
+        let line = line
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+            .replace("{command}", &command);
+
+        if line.contains("{input}") || line.contains("{sha256}") {
+            for input in inputs {
+                let hash = Sha256::digest(read(input)?)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<String>();
+                rv += &line
+                    .replace("{input}", &input.to_string_lossy())
+                    .replace("{sha256}", &hash);
+                rv += "\n";
+            }
+        } else {
+            rv += &line;
+            rv += "\n";
         }
-        _ => Ok(None),
     }
+
+    Ok(rv)
+}
+
+/// What the entries of a table hold
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TableFormat {
+    /// The address of each row function
+    Word,
+    /// The halfword offset of each row function from the table, which the row functions add to
+    /// --base-register
+    Half,
+}
+
+/// What `build` writes to its outfile
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// GNU assembler source
+    Asm,
+    /// The tables and row functions assembled at --vram, ready to splice into a ROM
+    Bin,
+    /// A relocatable MIPS object defining the same symbols as the assembly, ready to link
+    Elf,
+    /// A Rust module of the glyph bitmaps and a safe wrapper around the draw entry point of the
+    /// assembly built with --entry
+    Rust,
+}
+
+/// How `build` orders the functions of rows only the glyphs use
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExtraRowOrder {
+    /// The order the glyphs first use them in
+    FirstSeen,
+    /// By their pixels, so editing the font doesn't reshuffle the rest
+    Sorted,
+    /// The rows of --extra-order-file first, then any others in first-seen order
+    FromFile,
 }
 
-fn extract(
-    data: &[u8],
-    vram: u32,
-    num_chars: usize,
-    extra_offset: usize,
-) -> Result<(Vec<u8>, Vec<u8>)> {
-    let offsets_len = num_chars * 9 * size_of::<u32>() * 2;
+/// Where and how output files are written
+struct Output {
+    dir: PathBuf,
+    force: bool,
+}
 
-    let data_vram = vram + offsets_len as u32;
+impl Output {
+    /// Write to a temporary file next to `path` and rename it into place, so an interrupted
+    /// run never leaves a truncated output behind
+    fn commit(&self, path: &Path, save: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+        let path = self.dir.join(path);
+
+        if !self.force && path.exists() {
+            bail!(
+                "{} already exists (pass --force to overwrite it)",
+                path.display()
+            );
+        }
 
-    let mut cursor = Cursor::new(&data[..offsets_len]);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        if !dir.as_os_str().is_empty() {
+            create_dir_all(dir)
+                .with_context(|| format!("couldn't create directory {}", dir.display()))?;
+        }
 
-    let mut offsets = vec![];
-    while let Ok(offset) = cursor.read_u32::<BE>() {
-        offsets.push(offset - data_vram)
+        let Some(name) = path.file_name() else {
+            bail!("{} is not a file path", path.display());
+        };
+        let mut temp = std::ffi::OsString::from(".");
+        temp.push(name);
+        temp.push(format!(".{}.tmp", std::process::id()));
+        let temp = dir.join(temp);
+
+        let result = save(&temp).and_then(|()| Ok(rename(&temp, &path)?));
+        if result.is_err() {
+            let _ = remove_file(&temp);
+        }
+        result.with_context(|| format!("couldn't write {}", path.display()))
     }
 
-    let mut cursor = Cursor::new(&data[offsets_len..]);
+    fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+        if is_stdio(path) {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(contents.as_ref())?;
+            return Ok(stdout.flush()?);
+        }
 
-    let mut font: Vec<u8> = vec![];
+        self.commit(path, |temp| Ok(write(temp, contents)?))
+    }
 
-    for chunk in offsets.chunks(9).collect::<Vec<_>>().chunks(2) {
-        if let [block, _] = chunk {
-            for offset in &block[..8] {
-                cursor.set_position(*offset as u64);
-                if let Some(l) = parse_function(&mut cursor)? {
-                    font.extend(l.iter());
-                }
-            }
+    /// Save a grayscale image in the format its extension names, or as a PNG on stdout
+    fn image(&self, path: &Path, pixels: &[u8], width: u32) -> Result<()> {
+        let height = pixels.len() as u32 / width;
+        if is_stdio(path) {
+            let mut png = Cursor::new(vec![]);
+            image::write_buffer_with_format(
+                &mut png,
+                pixels,
+                width,
+                height,
+                image::ColorType::L8,
+                image::ImageFormat::Png,
+            )?;
+            return self.write(path, png.into_inner());
         }
+
+        let format = image::ImageFormat::from_path(path)?;
+        self.commit(path, |temp| {
+            Ok(image::save_buffer_with_format(
+                temp,
+                pixels,
+                width,
+                height,
+                image::ColorType::L8,
+                format,
+            )?)
+        })
     }
+}
 
-    let mut extra = vec![];
+fn provenance(template: Option<&Path>, enabled: bool, inputs: &[&Path]) -> Result<String> {
+    match (template, enabled) {
+        (Some(template), _) => header(&String::from_utf8(read(template)?)?, inputs),
+        (None, true) => header(HEADER, inputs),
+        (None, false) => Ok(String::new()),
+    }
+}
 
-    cursor.set_position(extra_offset as u64);
-    while (cursor.position() as usize) < data.len() - offsets_len {
-        if let Some(l) = parse_function(&mut cursor)? {
-            extra.extend(l.iter());
+/// A binary in big-endian order from `rom_offset` on, detecting the order of N64 ROMs unless
+/// `byte_order` is given
+fn read_dump(
+    path: &Path,
+    byte_order: Option<rom::ByteOrder>,
+    rom_offset: Option<usize>,
+) -> Result<Vec<u8>> {
+    let mut data = read(path)?;
+
+    let order = byte_order.unwrap_or_else(|| {
+        let detected = rom::ByteOrder::detect(&data).unwrap_or(rom::ByteOrder::Big);
+        match detected {
+            rom::ByteOrder::Big => {}
+            rom::ByteOrder::Byteswapped => {
+                eprintln!("note: reading byteswapped (.v64) input as big-endian")
+            }
+            rom::ByteOrder::Little => {
+                eprintln!("note: reading little-endian (.n64) input as big-endian")
+            }
         }
+        detected
+    });
+    order.normalize(&mut data);
+
+    if let Some(offset) = rom_offset {
+        if offset >= data.len() {
+            bail!(
+                "ROM offset 0x{offset:X} is past the end of the input file (0x{:X} bytes)",
+                data.len()
+            );
+        }
+        data.drain(..offset);
     }
 
-    Ok((font, extra))
+    Ok(data)
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    if args.timings {
+        timings::enable();
+    }
+
+    let rv = run(args);
+
+    if let Some(report) = timings::report() {
+        eprint!("{report}");
+    }
+
+    rv
+}
+
+fn run(args: Args) -> Result<()> {
+    let output = Output {
+        dir: args.out_dir.clone().unwrap_or_default(),
+        force: args.force,
+    };
+
     match args.command {
-        Command::Build {
+        Command::Build(BuildArgs {
             first_label,
             second_label,
             matching,
-        } => {
-            let infile = image::open(args.infile)?;
-            assert_eq!(infile.width(), 8);
-            assert_eq!(infile.height() % 8, 0);
-            let bw = infile.to_luma8();
-
-            let extra = if matching {
-                let extra = image::open(args.extra)?;
-                assert_eq!(extra.width(), 8);
-                assert_eq!(extra.height() % 8, 0);
-                let bw = extra.to_luma8();
-                Some(bw)
-            } else {
-                None
+            no_quirks,
+            quirk,
+            patches,
+            target,
+            target_base,
+            write_patches,
+            preset,
+            glyph_width,
+            glyph_height,
+            fb_width,
+            pixel_format,
+            registers,
+            table_format,
+            base_register,
+            numeric_registers,
+            raw,
+            bit_order,
+            planes,
+            plane_layout,
+            num_chars,
+            extras,
+            extra_list,
+            share,
+            chained,
+            entry,
+            glyph_entries,
+            colors,
+            incbin,
+            glyph_labels: labels,
+            charmap,
+            subset,
+            order,
+            extra_order,
+            extra_order_file,
+            extra_layout,
+            assembler,
+            macro_style,
+            function_directives,
+            prologue,
+            epilogue,
+            row_end,
+            verify,
+            dot,
+            stats,
+            c_header,
+            linker_script,
+            vram,
+            object,
+            emit,
+        }) => {
+            if matches!(emit, Emit::Bin | Emit::Elf) {
+                let text_only = [
+                    ("--chained", chained),
+                    ("--entry", entry),
+                    ("--glyph-entries", glyph_entries),
+                    ("--incbin", incbin.is_some()),
+                ];
+                if let Some((flag, _)) = text_only.iter().find(|(_, set)| *set) {
+                    bail!("{flag} has no binary form; build it with --emit asm");
+                }
+            }
+            if emit == Emit::Rust && chained {
+                bail!("--emit rust wraps the table of row functions, which --chained replaces");
+            }
+
+            let preset = preset.as_deref().map(presets::find).transpose()?;
+            let stride = match fb_width {
+                Some(width) => pixel_format.stride(width).with_context(|| {
+                    format!(
+                        "a {width}-pixel framebuffer of {}-byte pixels is too wide to advance a \
+                         row with addi",
+                        pixel_format.size()
+                    )
+                })?,
+                None => preset.map_or(pixel_format.default_stride(), |p| p.stride),
             };
+            if (stride as usize) < glyph_width * pixel_format.size() {
+                bail!(
+                    "{glyph_width}-pixel glyphs don't fit a {}-pixel framebuffer",
+                    stride as usize / pixel_format.size()
+                );
+            }
 
-            let out = build(
-                bw.as_bytes(),
-                &first_label,
-                &second_label,
-                extra.as_deref().map(EncodableLayout::as_bytes),
-            )?;
+            let load = |path: &Path| -> Result<Vec<u8>> {
+                if is_bdf(path) {
+                    let text = String::from_utf8(read(path)?)?;
+                    let (glyphs, skipped) = bdf::decode(&text, glyph_width, glyph_height)
+                        .with_context(|| format!("reading {}", path.display()))?;
+                    if !skipped.is_empty() {
+                        eprintln!(
+                            "warning: left out {} glyphs of {}, since they don't fit \
+                             {glyph_width}x{glyph_height}: {}",
+                            skipped.len(),
+                            path.display(),
+                            skipped.join(", ")
+                        );
+                    }
+                    return Ok(glyphs);
+                }
+                if is_psf(path) {
+                    let (glyphs, _) = psf::decode(&read(path)?, glyph_width, glyph_height)
+                        .with_context(|| format!("reading {}", path.display()))?;
+                    return Ok(glyphs);
+                }
+                if is_txt(path) {
+                    let text = String::from_utf8(read(path)?)?;
+                    return txt::from_txt(&text, glyph_width, glyph_height)
+                        .with_context(|| format!("reading {}", path.display()));
+                }
+                if !is_json(path) {
+                    let size = (glyph_width as u32, glyph_height as u32);
+                    let (pixels, width) = open_image(path, None, size, args.key_color)?;
+                    return Ok(grid_to_strip(&pixels, width, glyph_width, glyph_height));
+                }
 
-            write(args.outfile, out)?;
-        }
-        Command::Extract {
-            vram,
-            num_chars,
-            extra_offset,
-        } => {
-            let infile = read(args.infile)?;
-            let (out, extra) = extract(&infile, vram, num_chars, extra_offset)?;
-
-            image::save_buffer(
-                args.outfile,
-                &out,
-                8,
-                (out.len() / 8) as u32,
-                image::ColorType::L8,
-            )?;
+                let text = String::from_utf8(read(path)?)?;
+                let (glyphs, extracted) = json::from_json(&text, glyph_width, glyph_height)?;
+                if let Some(extracted) = extracted
+                    && extracted != stride
+                {
+                    eprintln!(
+                        "warning: {} was extracted from code with a stride of {extracted} bytes, \
+                         but this build uses {stride}",
+                        path.display(),
+                    );
+                }
+                Ok(glyphs)
+            };
+            let mut glyphs = match raw {
+                true => {
+                    let format = raw::Format {
+                        bit_order,
+                        planes: planes as usize,
+                        layout: plane_layout,
+                    };
+                    raw::decode(&read(&args.infile)?, format, glyph_width, glyph_height)?
+                }
+                false => load(&args.infile)?,
+            };
 
-            image::save_buffer(
-                args.extra,
-                &extra,
-                8,
-                (extra.len() / 8) as u32,
-                image::ColorType::L8,
-            )?;
+            let matching = matching || preset.is_some_and(|p| p.matching);
+            let database = match &patches {
+                Some(path) => quirks::parse(&String::from_utf8(read(path)?)?)
+                    .with_context(|| format!("reading {}", path.display()))?,
+                None => quirks::bundled(),
+            };
+            let firmware = preset
+                .map(|p| quirks::firmware_in(&database, p.name))
+                .unwrap_or_default();
+            let quirks = if !quirk.is_empty() {
+                quirk
+                    .iter()
+                    .map(|name| quirks::find_in(&database, name))
+                    .collect::<Result<Vec<_>>>()?
+            } else if matching && !no_quirks {
+                match preset {
+                    Some(_) => firmware.quirks.clone(),
+                    None => quirks::all_in(&database),
+                }
+            } else {
+                vec![]
+            };
+            if glyph_width != 8 && (preset.is_some() || !quirks.is_empty()) {
+                bail!(
+                    "presets and quirks describe 8-pixel-wide firmware fonts, not {glyph_width}-pixel \
+                     ones; pass --no-quirks for a wide matching build"
+                );
+            }
+            if let Some((single, pair)) = colors
+                && pixel_format == PixelFormat::Rgba16
+                && single.max(pair) > 0xFFFF
+            {
+                bail!("colors of 16-bit pixels must be at most 0xFFFF");
+            }
+            let registers = Registers {
+                base: (table_format == TableFormat::Half).then_some(base_register),
+                ..registers
+            };
+            if registers.base.is_some() {
+                if chained || !share.is_empty() {
+                    bail!("--chained and --share build tables of addresses");
+                }
+                if emit == Emit::Elf {
+                    bail!("--emit elf writes tables of addresses; assemble the source instead");
+                }
+            }
+            registers.check(entry || glyph_entries)?;
+
+            // the hand patches only replace stores, which a table of offsets leaves alone
+            let custom = Registers {
+                base: None,
+                ..registers
+            } != Registers::default();
+            if custom && !quirks.is_empty() {
+                bail!(
+                    "the hand patches store from the default registers; pass --no-quirks to \
+                     build with --registers"
+                );
+            }
+            if pixel_format != PixelFormat::Rgba16 && (preset.is_some() || !quirks.is_empty()) {
+                bail!(
+                    "presets and quirks describe fonts drawing 16-bit pixels; pass --no-quirks for \
+                     a 32-bit matching build"
+                );
+            }
+
+            // patches derived from the target store from the registers the build uses
+            let mut quirks = quirks;
+            if let Some(path) = &target {
+                // clap requires --vram with --target
+                let vram = vram.unwrap();
+                if glyph_width != 8 || pixel_format != PixelFormat::Rgba16 {
+                    bail!(
+                        "--target derives the patches of 8-pixel-wide fonts drawing 16-bit pixels"
+                    );
+                }
+                let data = read(path)?;
+                let base = target_base.unwrap_or(vram);
+                let Some(data) = vram
+                    .checked_sub(base)
+                    .and_then(|start| data.get(start as usize..))
+                else {
+                    bail!("table address 0x{vram:08X} is outside {}", path.display());
+                };
+                let (derived, notes) = quirks::derive(data, vram, glyph_height, stride, registers)
+                    .with_context(|| format!("reading {}", path.display()))?;
+                for note in &notes {
+                    eprintln!("warning: {note}");
+                }
+                for quirk in &derived {
+                    eprintln!(
+                        "note: derived patch {} storing pair {} of {} with `{:1}`",
+                        quirk.name,
+                        quirk.pair,
+                        quirk.function(),
+                        quirk.instr
+                    );
+                }
+                if let Some(path) = &write_patches {
+                    output.write(path, quirks::source(&derived))?;
+                }
+                // the target's own patches replace any given for the same stores
+                quirks.retain(|q| {
+                    !derived
+                        .iter()
+                        .any(|d| d.function() == q.function() && d.pair == q.pair)
+                });
+                quirks.extend(derived);
+            }
+
+            if let Some(n) = num_chars {
+                let found = glyphs.len() / (glyph_width * glyph_height);
+                if found > n {
+                    eprintln!("warning: truncating {found} glyphs to {n}");
+                }
+                glyphs.resize(n * glyph_width * glyph_height, 0);
+            }
+
+            let mut extra_paths = extras;
+            if let Some(list) = &extra_list {
+                extra_paths.extend(read_extra_list(list)?);
+            }
+            if matching && extra_paths.is_empty() {
+                eprintln!(
+                    "warning: matching build without --extra; the firmware's extra lines are missing"
+                );
+            }
+
+            let mut shared = vec![];
+            let mut labels_seen = vec![first_label.clone(), second_label.clone()];
+            for (path, first_label, second_label) in &share {
+                for label in [first_label, second_label] {
+                    if !charmap::is_identifier(label) {
+                        bail!("{label:?} is not a valid label");
+                    }
+                    if labels_seen.contains(label) {
+                        bail!(
+                            "the shared font {} reuses the label {label}",
+                            path.display()
+                        );
+                    }
+                    labels_seen.push(label.clone());
+                }
+
+                shared.push(SharedFont {
+                    glyphs: load(path)?,
+                    first_label: first_label.clone(),
+                    second_label: second_label.clone(),
+                });
+            }
+
+            let mut inputs = vec![args.infile.as_path()];
+            inputs.extend(share.iter().map(|(path, ..)| path.as_path()));
+            inputs.extend(extra_list.as_deref());
+            inputs.extend(extra_paths.iter().map(PathBuf::as_path));
+
+            let mut extra = vec![];
+            for path in &extra_paths {
+                let width = glyph_width as u32;
+                extra.extend(open_image(path, Some(width), (width, 8), args.key_color)?.0);
+            }
+
+            timings::lap("image decode");
+
+            for warning in redundant_extras(&extra, glyph_width) {
+                eprintln!("warning: {warning}");
+            }
+
+            let order = match &order {
+                Some(path) => {
+                    inputs.push(path);
+                    Some(parse_order(&String::from_utf8(read(path)?)?))
+                }
+                None if matching && !firmware.order.is_empty() => Some(firmware.order.clone()),
+                None => None,
+            };
+            let extra_order_rows = match &extra_order_file {
+                Some(path) => {
+                    inputs.push(path);
+                    parse_extra_order(&String::from_utf8(read(path)?)?, glyph_width)
+                        .with_context(|| format!("reading {}", path.display()))?
+                }
+                None => vec![],
+            };
+            let extra_layout = match &extra_layout {
+                Some(path) => {
+                    inputs.push(path);
+                    Some(parse_extra_layout(&String::from_utf8(read(path)?)?))
+                }
+                None => None,
+            };
+
+            inputs.extend(
+                [&prologue, &epilogue, &row_end]
+                    .into_iter()
+                    .flatten()
+                    .map(PathBuf::as_path),
+            );
+            let template = |path: &Option<PathBuf>| -> Result<Option<String>> {
+                let text = path.as_ref().map(read).transpose()?;
+                Ok(text.map(String::from_utf8).transpose()?)
+            };
+            let prologue = template(&prologue)?;
+            let epilogue = template(&epilogue)?;
+            let row_end = template(&row_end)?;
+            if row_end
+                .as_ref()
+                .is_some_and(|text| !text.contains("row_end"))
+            {
+                bail!("the --row-end file doesn't define row_end");
+            }
+
+            let syntax = Syntax {
+                assembler,
+                numeric_registers,
+                macro_style,
+                function_directives,
+            };
+            syntax.check()?;
+            let default_prologue = syntax.prologue(vram);
+            let default_epilogue = syntax.epilogue();
+            let default_row_end = syntax.row_end(registers);
+
+            let header = provenance(
+                args.header_template.as_deref(),
+                args.header || args.reproducible,
+                &inputs,
+            )?;
+
+            let charmap = match &charmap {
+                Some(path) => Some(Charmap::parse(&String::from_utf8(read(path)?)?)?),
+                None if is_psf(&args.infile) => {
+                    psf::decode(&read(&args.infile)?, glyph_width, glyph_height)?.1
+                }
+                None => None,
+            };
+            let comments = match &charmap {
+                Some(charmap) => {
+                    glyph_comments(charmap, glyphs.len() / (glyph_width * glyph_height))
+                }
+                None => vec![],
+            };
+            let charmap = charmap.unwrap_or_else(Charmap::identity);
+            if let Some(path) = &subset {
+                let text = String::from_utf8(read(path)?)?;
+                let blanked;
+                (glyphs, blanked) =
+                    gsfont::subset(&glyphs, &charmap, &text, glyph_width, glyph_height);
+                eprintln!(
+                    "note: blanked {blanked} glyphs not used by {}",
+                    path.display()
+                );
+            }
+            let names = if labels || glyph_entries {
+                glyph_labels(
+                    &charmap,
+                    glyphs.len() / (glyph_width * glyph_height),
+                    &first_label,
+                    &second_label,
+                )?
+            } else {
+                vec![]
+            };
+            let labels = if labels { names.clone() } else { vec![] };
+            let glyph_entries = if glyph_entries { names } else { vec![] };
+
+            let incbin_path = incbin
+                .as_deref()
+                .map(|p| p.to_str().context("the --incbin path must be valid UTF-8"))
+                .transpose()?;
+            let options = BuildOptions {
+                first_label: &first_label,
+                second_label: &second_label,
+                extra: &extra,
+                quirks: &quirks,
+                incbin: incbin_path,
+                order: order.as_deref(),
+                extra_order: match extra_order {
+                    ExtraRowOrder::FirstSeen => ExtraOrder::FirstSeen,
+                    ExtraRowOrder::Sorted => ExtraOrder::Sorted,
+                    ExtraRowOrder::FromFile => ExtraOrder::Listed(&extra_order_rows),
+                },
+                glyph_labels: &labels,
+                glyph_comments: &comments,
+                glyph_width,
+                glyph_height,
+                pixel_format,
+                stride,
+                entry,
+                glyph_entries: &glyph_entries,
+                glyph_colors: colors,
+                shared: &shared,
+                extra_layout: extra_layout.as_deref(),
+                framing: Framing {
+                    prologue: prologue.as_deref().unwrap_or(&default_prologue),
+                    epilogue: epilogue.as_deref().unwrap_or(&default_epilogue),
+                    row_end: row_end.as_deref().unwrap_or(&default_row_end),
+                },
+                registers,
+                syntax,
+            };
+            let run = || match chained {
+                true => chained_build(&glyphs, &options),
+                false => build(&glyphs, &options),
+            };
+
+            let built = run()?;
+            let out = header.clone() + &built.source();
+
+            if args.reproducible {
+                let again = run()?;
+                check_reproducible(
+                    out.as_bytes(),
+                    (header.clone() + &again.source()).as_bytes(),
+                    &inputs,
+                )?;
+                check_reproducible(&built.bin, &again.bin, &inputs)?;
+            }
+
+            for quirk in quirks.iter().filter(|q| built.quirks.contains(&q.name)) {
+                eprintln!(
+                    "note: quirk {} stores pair {} of {} with `{:1}`",
+                    quirk.name,
+                    quirk.pair,
+                    quirk.function(),
+                    quirk.instr
+                );
+            }
+
+            if verify {
+                {
+                    // the round trip's own build and extract phases all count as verifying
+                    let _suspended = timings::suspend();
+                    roundtrip::roundtrip(&glyphs, &options)?;
+                }
+                timings::lap("verify");
+            }
+
+            if let Some(path) = dot {
+                let graph = usage_graph(&glyphs, &extra, glyph_width, glyph_height);
+                output.write(&path, graph)?;
+            }
+
+            if let Some(path) = stats {
+                let (functions, table) = layout(&glyphs, &options)?;
+                output.write(&path, stats_source(&functions, &table, &options))?;
+            }
+
+            if let Some(path) = c_header {
+                let guard = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .chars()
+                    .map(|c| match c.is_ascii_alphanumeric() {
+                        true => c.to_ascii_uppercase(),
+                        false => '_',
+                    })
+                    .collect::<String>();
+                let num_chars = glyphs.len() / (glyph_width * glyph_height);
+                let source = c_header_source(&options, &built, num_chars, chained, &guard);
+                output.write(&path, header.clone() + &source)?;
+            }
+
+            if let (Some(path), Some(vram)) = (linker_script, vram) {
+                let object = object.unwrap_or_else(|| args.outfile.with_extension("o"));
+                let script = linker_script_source(
+                    object
+                        .to_str()
+                        .context("the --object path must be valid UTF-8")?,
+                    vram,
+                    &first_label,
+                    &second_label,
+                    glyph_height,
+                    registers,
+                );
+                output.write(&path, header.clone() + &script)?;
+            }
+
+            match (emit, vram) {
+                (Emit::Bin, Some(vram)) => {
+                    let bin = build_binary(&glyphs, &options, vram)?;
+                    if args.reproducible {
+                        let again = build_binary(&glyphs, &options, vram)?;
+                        check_reproducible(&bin, &again, &inputs)?;
+                    }
+                    output.write(&args.outfile, bin)?;
+                }
+                (Emit::Elf, _) => {
+                    let object = build_object(&glyphs, &options)?;
+                    if args.reproducible {
+                        let again = build_object(&glyphs, &options)?;
+                        check_reproducible(&object, &again, &inputs)?;
+                    }
+                    output.write(&args.outfile, object)?;
+                }
+                (Emit::Rust, _) => {
+                    let module = rust_module_source(&glyphs, &options);
+                    output.write(&args.outfile, header.clone() + &module)?;
+                }
+                // clap requires --vram for binaries
+                _ => output.write(&args.outfile, out)?,
+            }
+
+            if let Some(path) = incbin {
+                output.write(&path, &built.bin)?;
+            }
+            timings::lap("write");
+        }
+        Command::Extract {
+            vram,
+            symbol,
+            code,
+            base,
+            rom_offset,
+            byte_order,
+            num_chars,
+            extra_offset,
+            first,
+            count,
+            extra,
+            glyph_width,
+            glyph_height,
+            no_row_end,
+            pixel_format,
+            registers,
+            table_format,
+            base_register,
+            shades,
+            columns,
+            chained,
+            table,
+            order,
+            extra_layout,
+            json,
+            metadata,
+            symbol_addrs,
+            splat,
+            object,
+            html,
+            svg,
+            bdf,
+            txt,
+            psf,
+            psf1,
+            charmap,
+            svg_dir,
+            first_label,
+            second_label,
+            dir,
+            also,
+        } => {
+            let infile = read_dump(&args.infile, byte_order, rom_offset)?;
+            timings::lap("read");
+
+            let registers = Registers {
+                base: (table_format == TableFormat::Half).then_some(base_register),
+                ..registers
+            };
+            if chained && registers.base.is_some() {
+                bail!("chained tables hold addresses, not halfword offsets");
+            }
+
+            let (infile, vram, base, num_chars) = match &symbol {
+                Some(name) => {
+                    let located = elf::locate(&infile, name)
+                        .with_context(|| format!("reading {}", args.infile.display()))?;
+                    let glyph_size = match chained {
+                        true => 2 * size_of::<u32>(),
+                        false => {
+                            2 * (glyph_height + usize::from(!no_row_end)) * registers.entry_size()
+                        }
+                    };
+                    let size = located.size as usize;
+                    let whole = size > 0 && size.is_multiple_of(glyph_size);
+                    let num_chars = num_chars.or(whole.then_some(size / glyph_size));
+                    let contents = located.contents.to_vec();
+                    (contents, Some(located.vram), Some(located.base), num_chars)
+                }
+                None => (infile, vram, base, num_chars),
+            };
+
+            let vram = match (vram, code, base) {
+                (Some(vram), _, _) => vram,
+                (None, Some(code), Some(base)) => {
+                    let vram = trace_table(&infile, base, code)?;
+                    eprintln!("table at 0x{vram:08X}");
+                    vram
+                }
+                _ => unreachable!(),
+            };
+
+            let base = base.unwrap_or(vram);
+            let table_data = |vram: u32| match vram
+                .checked_sub(base)
+                .filter(|&o| (o as usize) < infile.len())
+            {
+                Some(start) => Ok(&infile[start as usize..]),
+                None => Err(anyhow!(
+                    "table address 0x{vram:08X} is outside the input file"
+                )),
+            };
+
+            let num_chars = match num_chars {
+                Some(num_chars) => num_chars,
+                None => {
+                    let data = table_data(vram)?;
+                    let num_chars =
+                        infer_num_chars(data, vram, glyph_height, !no_row_end, chained, registers)?;
+                    eprintln!("note: found {num_chars} glyphs in the table at 0x{vram:08X}");
+                    num_chars
+                }
+            };
+
+            let count = count.unwrap_or(num_chars.saturating_sub(first));
+
+            let mapped = charmap.is_some();
+            let charmap = match &charmap {
+                Some(path) => Charmap::parse(&String::from_utf8(read(path)?)?)?,
+                None => Charmap::identity(),
+            };
+            let comments = |first: usize, count: usize| match mapped {
+                true => glyph_comments(&charmap, first + count).split_off(first),
+                false => vec![],
+            };
+            // the outputs that go by the charmap number the glyphs from --first
+            let ranged;
+            let charmap = match first {
+                0 => &charmap,
+                _ => {
+                    ranged = charmap.range(first, count);
+                    &ranged
+                }
+            };
+            let named_chars = mapped.then_some(charmap);
+            let write_bdf = |path: &Path, font: &[u8]| {
+                let bdf = bdf::encode(font, glyph_width, glyph_height, charmap, &first_label);
+                output.write(path, bdf)
+            };
+            let write_psf = |path: &Path, font: &[u8]| {
+                let psf = psf::encode(font, glyph_width, glyph_height, charmap, psf1)?;
+                output.write(path, psf)
+            };
+
+            let sheet = |font: &[u8]| match columns {
+                Some(c) => (
+                    strip_to_grid(font, c, glyph_width, glyph_height),
+                    c * glyph_width as u32,
+                ),
+                None => (font.to_vec(), glyph_width as u32),
+            };
+
+            if chained {
+                let extraction = extract_chained(
+                    table_data(vram)?,
+                    vram,
+                    num_chars,
+                    glyph_width,
+                    glyph_height,
+                    pixel_format,
+                    registers,
+                )?;
+
+                if let Some(path) = json {
+                    let font = &extraction.font;
+                    let json = json::to_json(
+                        font,
+                        glyph_width,
+                        glyph_height,
+                        extraction.stride,
+                        named_chars,
+                    );
+                    output.write(&path, json)?;
+                }
+                if let Some(path) = &bdf {
+                    write_bdf(path, &extraction.font)?;
+                }
+                if let Some(path) = &psf {
+                    write_psf(path, &extraction.font)?;
+                }
+                if let Some(path) = &txt {
+                    let txt = txt::to_txt(&extraction.font, glyph_width, glyph_height, named_chars);
+                    output.write(path, txt)?;
+                }
+                let font = shades.apply(&extraction.font);
+                if let Some(path) = svg {
+                    let sheet = strip_to_grid(&font, 16, glyph_width, glyph_height);
+                    output.write(
+                        &path,
+                        report::svg(&sheet, 16 * glyph_width as u32, 4) + "\n",
+                    )?;
+                }
+                timings::lap("write");
+                let (pixels, width) = sheet(&font);
+                output.image(&args.outfile, &pixels, width)?;
+                timings::lap("image encode");
+                return Ok(());
+            }
+
+            let extra_offset = match extra_offset {
+                Some(extra_offset) => extra_offset,
+                None => {
+                    let options = ExtractOptions {
+                        vram,
+                        num_chars,
+                        first: 0,
+                        count: num_chars,
+                        extra_offset: 0,
+                        glyph_width,
+                        glyph_height,
+                        row_end: !no_row_end,
+                        pixel_format,
+                        registers,
+                    };
+                    let extra_offset = infer_extra_offset(table_data(vram)?, &options)?;
+                    eprintln!("note: found the extra lines at code offset 0x{extra_offset:X}");
+                    extra_offset
+                }
+            };
+            let mut tables = vec![(vram, num_chars, first, count, extra_offset)];
+            tables.extend(also.into_iter().map(|(v, n, e)| (v, n, 0, n, e)));
+            let mut referenced = vec![];
+
+            for (vram, num_chars, first, count, extra_offset) in tables {
+                let data = table_data(vram)?;
+
+                let options = ExtractOptions {
+                    vram,
+                    num_chars,
+                    first,
+                    count,
+                    extra_offset,
+                    glyph_width,
+                    glyph_height,
+                    row_end: !no_row_end,
+                    pixel_format,
+                    registers,
+                };
+                let extraction = extract(data, &options)?;
+
+                for anomaly in &extraction.anomalies {
+                    eprintln!("warning: {anomaly}");
+                }
+                if !extraction.anomalies.is_empty() {
+                    eprintln!(
+                        "note: table anomalies at 0x{vram:08X}: {}; the glyphs are read from the \
+                         rows of their single blocks",
+                        extraction.anomalies.len()
+                    );
+                }
+
+                if let Some(stride) = extraction.stride
+                    && stride != pixel_format.default_stride()
+                {
+                    eprintln!(
+                        "note: the row functions at 0x{vram:08X} use a stride of {stride} bytes \
+                         ({} pixels of {} bytes); build with --fb-width {0} to match",
+                        stride / pixel_format.size() as i16,
+                        pixel_format.size()
+                    );
+                }
+
+                if args.reproducible {
+                    let again = extract(data, &options)?;
+                    check_reproducible(&extraction.font, &again.font, &[])?;
+                    check_reproducible(&extraction.extra, &again.extra, &[])?;
+                }
+
+                let named = |prefix: &str, extension: &str| {
+                    dir.as_ref()
+                        .map(|dir| dir.join(format!("{prefix}_{vram:08X}.{extension}")))
+                };
+                let (
+                    outfile,
+                    extra,
+                    table,
+                    order,
+                    extra_layout,
+                    json,
+                    metadata,
+                    symbol_addrs,
+                    splat,
+                    object,
+                ) = match &dir {
+                    Some(_) => (
+                        named("font", "png").unwrap(),
+                        named("extra", "png"),
+                        named("table", "s"),
+                        named("order", "txt"),
+                        named("extra_layout", "txt"),
+                        named("font", "json"),
+                        named("metadata", "json"),
+                        named("symbol_addrs", "txt"),
+                        named("splat", "yaml").filter(|_| symbol.is_none()),
+                        named("font", "o").filter(|_| registers.base.is_none()),
+                    ),
+                    None => (
+                        args.outfile.clone(),
+                        extra.clone(),
+                        table.clone(),
+                        order.clone(),
+                        extra_layout.clone(),
+                        json.clone(),
+                        metadata.clone(),
+                        symbol_addrs.clone(),
+                        splat.clone(),
+                        object.clone(),
+                    ),
+                };
+
+                if let Some(path) = table {
+                    let comments = comments(first, count);
+                    let out = String::from(PROLOGUE)
+                        + &table_source(
+                            &extraction.table,
+                            (&first_label, &second_label),
+                            &[],
+                            &comments,
+                            glyph_height,
+                            registers,
+                            Syntax::default(),
+                        )
+                        + EPILOGUE;
+                    output.write(&path, out)?;
+                }
+
+                if let Some(path) = order {
+                    output.write(&path, order_source(&extraction.functions))?;
+                }
+
+                if let Some(path) = named("charmap", "txt").filter(|_| mapped) {
+                    output.write(&path, charmap.source())?;
+                }
+
+                if let Some(path) = extra_layout {
+                    output.write(&path, extra_layout_source(&extraction.extra_layout))?;
+                }
+
+                if let Some(path) = json {
+                    let font = &extraction.font;
+                    let json = json::to_json(
+                        font,
+                        glyph_width,
+                        glyph_height,
+                        extraction.stride,
+                        named_chars,
+                    );
+                    output.write(&path, json)?;
+                }
+
+                if let Some(path) = metadata {
+                    output.write(&path, json::metadata_json(&extraction))?;
+                }
+
+                if let Some(path) = symbol_addrs {
+                    let labels = (first_label.as_str(), second_label.as_str());
+                    let source = symbol_addrs_source(&extraction, labels, registers);
+                    output.write(&path, source)?;
+                }
+
+                if let Some(path) = splat {
+                    let rom = rom_offset.unwrap_or(0) as u32 + (vram - base);
+                    output.write(&path, splat_source(&extraction, rom, &first_label))?;
+                }
+
+                if let Some(path) = object {
+                    let labels = (first_label.as_str(), second_label.as_str());
+                    let object = extraction_object(data, &extraction, labels, registers)?;
+                    output.write(&path, object)?;
+                }
+
+                if let Some(path) = &html {
+                    output.write(path, report::html(&extraction))?;
+                }
+
+                if let Some(path) = &bdf {
+                    write_bdf(path, &extraction.font)?;
+                }
+                if let Some(path) = &psf {
+                    write_psf(path, &extraction.font)?;
+                }
+                if let Some(path) = &txt {
+                    let txt = txt::to_txt(&extraction.font, glyph_width, glyph_height, named_chars);
+                    output.write(path, txt)?;
+                }
+
+                let font = shades.apply(&extraction.font);
+
+                if let Some(path) = &svg {
+                    let sheet = strip_to_grid(&font, 16, glyph_width, glyph_height);
+                    output.write(path, report::svg(&sheet, 16 * glyph_width as u32, 4) + "\n")?;
+                }
+
+                if let Some(dir) = &svg_dir {
+                    let glyphs = font.chunks_exact(glyph_width * glyph_height);
+                    for (index, glyph) in glyphs.enumerate() {
+                        output.write(
+                            &dir.join(format!("glyph_{index:02X}.svg")),
+                            report::glyph_svg(glyph, glyph_width, 4) + "\n",
+                        )?;
+                    }
+                }
+
+                timings::lap("write");
+
+                let (pixels, width) = sheet(&font);
+                output.image(&outfile, &pixels, width)?;
+
+                if let Some(path) = extra {
+                    let extra = shades.apply(&extraction.extra);
+                    output.image(&path, &extra, glyph_width as u32)?;
+                }
+                timings::lap("image encode");
+
+                referenced.push((
+                    vram,
+                    extraction.addresses.into_iter().collect::<HashSet<_>>(),
+                ));
+            }
+
+            for (i, (a, functions_a)) in referenced.iter().enumerate() {
+                for (b, functions_b) in &referenced[i + 1..] {
+                    let count = functions_a.intersection(functions_b).count();
+                    if count > 0 {
+                        eprintln!(
+                            "note: the tables at 0x{a:08X} and 0x{b:08X} share {count} row functions"
+                        );
+                    }
+                }
+            }
+        }
+        Command::Compare {
+            other,
+            vram,
+            num_chars,
+            extra_offset,
+            other_vram,
+            other_num_chars,
+            other_extra_offset,
+            glyph_width,
+            glyph_height,
+            pixel_format,
+            registers,
+        } => {
+            let a = ExtractOptions {
+                vram,
+                num_chars,
+                first: 0,
+                count: num_chars,
+                extra_offset,
+                glyph_width,
+                glyph_height,
+                row_end: true,
+                pixel_format,
+                registers,
+            };
+            let b = ExtractOptions {
+                vram: other_vram.unwrap_or(vram),
+                num_chars: other_num_chars.unwrap_or(num_chars),
+                first: 0,
+                count: other_num_chars.unwrap_or(num_chars),
+                extra_offset: other_extra_offset.unwrap_or(extra_offset),
+                glyph_width,
+                glyph_height,
+                row_end: true,
+                pixel_format,
+                registers,
+            };
+            let a = extract(&read(&args.infile)?, &a)?;
+            let b = extract(&read(other)?, &b)?;
+
+            output.write(&args.outfile, compare(&a, &b))?;
+        }
+        Command::Fmt {
+            keep_order,
+            numeric_registers,
+            registers,
+        } => {
+            registers.check(false)?;
+            let source = asm::parse(&String::from_utf8(read(&args.infile)?)?)?;
+            let recovered = recover(&source, registers)?;
+            let syntax = Syntax {
+                numeric_registers,
+                ..Syntax::default()
+            };
+            let row_end = syntax.row_end(registers);
+
+            let inputs = [args.infile.as_path()];
+            let header = provenance(
+                args.header_template.as_deref(),
+                args.header || args.reproducible,
+                &inputs,
+            )?;
+
+            let options = BuildOptions {
+                first_label: &recovered.first_label,
+                second_label: &recovered.second_label,
+                extra: &recovered.extra,
+                quirks: &recovered.quirks,
+                incbin: None,
+                order: keep_order.then_some(&recovered.order[..]),
+                extra_order: ExtraOrder::FirstSeen,
+                glyph_labels: &recovered.glyph_labels,
+                glyph_comments: &recovered.glyph_comments,
+                glyph_width: recovered.glyph_width,
+                glyph_height: recovered.glyph_height,
+                pixel_format: recovered.pixel_format,
+                stride: recovered.stride,
+                entry: recovered.entry,
+                glyph_entries: &recovered.glyph_entries,
+                glyph_colors: recovered.glyph_colors,
+                shared: &[],
+                extra_layout: None,
+                framing: Framing {
+                    row_end: &row_end,
+                    ..Framing::default()
+                },
+                registers,
+                syntax,
+            };
+            let run =
+                || build(&recovered.font, &options).map(|built| header.clone() + &built.source());
+
+            let out = run()?;
+
+            if args.reproducible {
+                check_reproducible(out.as_bytes(), run()?.as_bytes(), &inputs)?;
+            }
+
+            output.write(&args.outfile, out)?;
+        }
+        Command::Recover { extra, registers } => {
+            registers.check(false)?;
+            let source = asm::parse(&String::from_utf8(read(&args.infile)?)?)?;
+            let recovered = recover(&source, registers)?;
+            let font = recovered.font;
+            let (width, height) = (recovered.glyph_width, recovered.glyph_height);
+
+            if is_json(&args.outfile) {
+                output.write(
+                    &args.outfile,
+                    json::to_json(&font, width, height, None, None),
+                )?;
+            } else {
+                output.image(&args.outfile, &font, width as u32)?;
+            }
+
+            if let Some(path) = extra {
+                output.image(&path, &recovered.extra, width as u32)?;
+            }
+        }
+        Command::Localize {
+            corpus,
+            first_label,
+            second_label,
+            glyph_width,
+            glyph_height,
+            charmap,
+            source,
+            source_charmap,
+            image,
+            write_charmap,
+        } => {
+            let cell = glyph_width * glyph_height;
+            let mut glyphs = load_glyphs(&args.infile, glyph_width, glyph_height, args.key_color)?;
+            let text = String::from_utf8(read(&corpus)?)?;
+            let mut inputs = vec![args.infile.as_path(), corpus.as_path()];
+
+            let charmap = match &charmap {
+                Some(path) => {
+                    inputs.push(path);
+                    Charmap::parse(&String::from_utf8(read(path)?)?)?
+                }
+                None => Charmap::identity(),
+            };
+            let source = match &source {
+                Some(path) => {
+                    inputs.push(path);
+                    Some(load_glyphs(
+                        path,
+                        glyph_width,
+                        glyph_height,
+                        args.key_color,
+                    )?)
+                }
+                None => None,
+            };
+            let source_charmap = match &source_charmap {
+                Some(path) => {
+                    inputs.push(path);
+                    Charmap::parse(&String::from_utf8(read(path)?)?)?
+                }
+                None => Charmap::identity(),
+            };
+
+            let num_chars = glyphs.len() / cell;
+            let added = localize(&glyphs, &charmap, &text, glyph_width, glyph_height);
+
+            let mut entries = (0..num_chars)
+                .filter_map(|i| Some((i, charmap.char(i)?)))
+                .collect::<Vec<_>>();
+            for &(index, c) in &added {
+                if index >= glyphs.len() / cell {
+                    glyphs.resize((index + 1) * cell, 0);
+                }
+
+                eprintln!("note: {c:?} is glyph 0x{index:02X}");
+
+                let glyph = source.as_ref().and_then(|source| {
+                    let i = source_charmap.glyph(c)?;
+                    source.chunks_exact(cell).nth(i)
+                });
+                match glyph {
+                    Some(glyph) => glyphs[index * cell..][..cell].copy_from_slice(glyph),
+                    None => eprintln!("warning: no glyph for {c:?}, leaving 0x{index:02X} blank"),
+                }
+
+                entries.push((index, c));
+            }
+
+            let header = provenance(
+                args.header_template.as_deref(),
+                args.header || args.reproducible,
+                &inputs,
+            )?;
+            let options = BuildOptions {
+                first_label: &first_label,
+                second_label: &second_label,
+                extra: &[],
+                quirks: &[],
+                incbin: None,
+                order: None,
+                extra_order: ExtraOrder::FirstSeen,
+                glyph_labels: &[],
+                glyph_comments: &[],
+                glyph_width,
+                glyph_height,
+                pixel_format: PixelFormat::Rgba16,
+                stride: PixelFormat::Rgba16.default_stride(),
+                entry: false,
+                glyph_entries: &[],
+                glyph_colors: None,
+                shared: &[],
+                extra_layout: None,
+                framing: Framing::default(),
+                registers: Registers::default(),
+                syntax: Syntax::default(),
+            };
+            let built = build(&glyphs, &options)?;
+
+            output.write(&args.outfile, header + &built.source())?;
+            output.image(&image, &glyphs, glyph_width as u32)?;
+            output.write(&write_charmap, Charmap::from_entries(entries).source())?;
+        }
+        Command::Scan {
+            base,
+            rom_offset,
+            byte_order,
+            glyph_width,
+            glyph_height,
+            pixel_format,
+            registers,
+        } => {
+            let infile = read_dump(&args.infile, byte_order, rom_offset)?;
+            timings::lap("read");
+
+            let candidates = scan::scan(
+                &infile,
+                base,
+                glyph_width,
+                glyph_height,
+                pixel_format,
+                registers,
+            );
+            timings::lap("scan");
+
+            let mut out = String::new();
+            for candidate in &candidates {
+                out += &format!("0x{:08X}:{}", candidate.vram, candidate.num_chars);
+                if let Some(extra_offset) = candidate.extra_offset {
+                    out += &format!(":0x{extra_offset:X}");
+                }
+                out += "\n";
+            }
+            if candidates.is_empty() {
+                eprintln!("note: found no font tables");
+            }
+
+            output.write(&args.outfile, out)?;
+            timings::lap("write");
+        }
+        Command::Diff {
+            reference,
+            vram,
+            base,
+        } => {
+            let ours = match is_asm(&args.infile) {
+                true => {
+                    let source = asm::parse(&String::from_utf8(read(&args.infile)?)?)?;
+                    assemble_source(&source, vram)?
+                }
+                false => read(&args.infile)?,
+            };
+            let reference = read(&reference)?;
+            let base = base.unwrap_or(vram);
+            let Some(theirs) = vram
+                .checked_sub(base)
+                .and_then(|start| reference.get(start as usize..))
+            else {
+                bail!("table address 0x{vram:08X} is outside the reference");
+            };
+            let theirs = &theirs[..ours.len().min(theirs.len())];
+
+            output.write(&args.outfile, diff_words(&ours, theirs, vram))?;
+        }
+        Command::ExportScratch {
+            address,
+            base,
+            rom_offset,
+            byte_order,
+            name,
+            glyph_width,
+            pixel_format,
+            registers,
+            table_format,
+            base_register,
+            compiler,
+            compiler_flags,
+        } => {
+            let infile = read_dump(&args.infile, byte_order, rom_offset)?;
+            let registers = Registers {
+                base: (table_format == TableFormat::Half).then_some(base_register),
+                ..registers
+            };
+            let base = base.unwrap_or(address);
+            let Some(code) = address
+                .checked_sub(base)
+                .and_then(|start| infile.get(start as usize..))
+            else {
+                bail!("function address 0x{address:08X} is outside the input file");
+            };
+
+            let scratch = scratch::export(
+                code,
+                address,
+                name.as_deref(),
+                glyph_width,
+                pixel_format,
+                registers,
+                (&compiler, &compiler_flags),
+            )?;
+            output.write(&args.outfile, scratch)?;
+        }
+        Command::Print {
+            text,
+            glyph_width,
+            glyph_height,
+            charmap,
+        } => {
+            let glyphs = load_glyphs(&args.infile, glyph_width, glyph_height, args.key_color)?;
+            let charmap = match charmap {
+                Some(path) => Charmap::parse(&String::from_utf8(read(path)?)?)?,
+                None => Charmap::identity(),
+            };
+
+            let (out, missing) = render_text(&glyphs, glyph_width, glyph_height, &charmap, &text);
+            for c in missing {
+                eprintln!("warning: no glyph for {c:?}");
+            }
+            output.write(&args.outfile, out)?;
+        }
+        Command::BuildAll { makefile } => {
+            let manifest = manifest::parse(&String::from_utf8(read(&args.infile)?)?)?;
+            let base = match is_stdio(&args.infile) {
+                true => Path::new(""),
+                false => args.infile.parent().unwrap_or(Path::new("")),
+            };
+
+            let mut outputs = String::new();
+            let mut rules = String::new();
+            for font in &manifest.font {
+                let image = base.join(&font.image);
+                let outfile = base.join(&font.output);
+                let context = || format!("building {}", outfile.display());
+
+                let mut options = vec![];
+                options.extend([font.first_label.clone(), font.second_label.clone()]);
+                if let Some(preset) = &font.preset {
+                    options.extend(["--preset".to_string(), preset.clone()]);
+                }
+                options.extend(font.args.iter().cloned());
+                let mut build = FontArgs::try_parse_from(options)
+                    .with_context(context)?
+                    .build;
+                build.matching |= font.matching;
+                // the manifest's extra lines come before any in its other options
+                build
+                    .extras
+                    .splice(0..0, font.extra.iter().map(|extra| base.join(extra)));
+
+                // make rebuilds the font when the manifest or anything the build reads changes
+                let mut prerequisites = vec![args.infile.clone(), image.clone()];
+                prerequisites.extend(build.inputs().into_iter().map(Path::to_path_buf));
+                if let Some(list) = &build.extra_list {
+                    prerequisites.extend(read_extra_list(list).with_context(context)?);
+                }
+                prerequisites.extend(args.header_template.clone());
+                rules += &format!("{}:", outfile.display());
+                for (index, prerequisite) in prerequisites.iter().enumerate() {
+                    if !prerequisites[..index].contains(prerequisite) {
+                        rules += &format!(" {}", prerequisite.display());
+                    }
+                }
+                rules += "\n";
+
+                let nested = Args {
+                    command: Command::Build(build),
+                    infile: image.clone(),
+                    outfile: outfile.clone(),
+                    out_dir: args.out_dir.clone(),
+                    key_color: args.key_color,
+                    force: args.force,
+                    reproducible: args.reproducible,
+                    header: args.header,
+                    header_template: args.header_template.clone(),
+                    timings: args.timings,
+                };
+                run(nested).with_context(context)?;
+
+                outputs += &format!("{}\n", outfile.display());
+            }
+            if manifest.font.is_empty() {
+                eprintln!("warning: {} lists no fonts", args.infile.display());
+            }
+
+            if let Some(path) = makefile {
+                output.write(&path, rules)?;
+            }
+            output.write(&args.outfile, outputs)?;
+        }
+        Command::Show {
+            glyphs: indices,
+            glyph_width,
+            glyph_height,
+            braille,
+        } => {
+            let glyphs = load_glyphs(&args.infile, glyph_width, glyph_height, args.key_color)?;
+            let indices = match indices.is_empty() {
+                true => (0..glyphs.len() / (glyph_width * glyph_height)).collect(),
+                false => indices,
+            };
+
+            let out = show_glyphs(&glyphs, &indices, glyph_width, glyph_height, braille)?;
+            output.write(&args.outfile, out)?;
+        }
+        Command::Preview {
+            text,
+            glyph_width,
+            glyph_height,
+            charmap,
+            quirk,
+            preset,
+            registers,
+        } => {
+            registers.check(false)?;
+            let recovered = match is_asm(&args.infile) {
+                true => {
+                    let source = asm::parse(&String::from_utf8(read(&args.infile)?)?)?;
+                    Some(recover(&source, registers)?)
+                }
+                false => None,
+            };
+            let (glyphs, glyph_width, glyph_height) = match &recovered {
+                Some(recovered) => {
+                    let size = (recovered.glyph_width, recovered.glyph_height);
+                    let given = (
+                        glyph_width.unwrap_or(size.0),
+                        glyph_height.unwrap_or(size.1),
+                    );
+                    if given != size {
+                        bail!(
+                            "{} draws {}x{} glyphs, not {}x{}",
+                            args.infile.display(),
+                            size.0,
+                            size.1,
+                            given.0,
+                            given.1
+                        );
+                    }
+                    (recovered.font.clone(), size.0, size.1)
+                }
+                None => {
+                    let (width, height) = (glyph_width.unwrap_or(8), glyph_height.unwrap_or(8));
+                    let glyphs = load_glyphs(&args.infile, width, height, args.key_color)?;
+                    (glyphs, width, height)
+                }
+            };
+            let charmap = match charmap {
+                Some(path) => Charmap::parse(&String::from_utf8(read(path)?)?)?,
+                None => Charmap::identity(),
+            };
+            let quirks = match preset {
+                Some(name) => quirks::firmware(presets::find(&name)?.name).quirks,
+                None if quirk.is_empty() => recovered
+                    .as_ref()
+                    .map_or(vec![], |recovered| recovered.quirks.clone()),
+                None => quirk
+                    .iter()
+                    .map(|name| quirks::find(name))
+                    .collect::<Result<Vec<_>>>()?,
+            };
+            let pixel_format = recovered
+                .as_ref()
+                .map_or(PixelFormat::Rgba16, |recovered| recovered.pixel_format);
+            let stride = recovered
+                .as_ref()
+                .map_or(pixel_format.default_stride(), |recovered| recovered.stride);
+
+            let options = BuildOptions {
+                first_label: "font_single",
+                second_label: "font_double",
+                extra: &[],
+                quirks: &quirks,
+                incbin: None,
+                order: None,
+                extra_order: ExtraOrder::FirstSeen,
+                glyph_labels: &[],
+                glyph_comments: &[],
+                glyph_width,
+                glyph_height,
+                pixel_format,
+                stride,
+                entry: false,
+                glyph_entries: &[],
+                glyph_colors: None,
+                shared: &[],
+                extra_layout: None,
+                framing: Framing::default(),
+                registers,
+                syntax: Syntax::default(),
+            };
+            let (single, double) = roundtrip::render(&glyphs, &options)?;
+            let (pixels, width, missing) =
+                preview(&single, &double, &charmap, &text, glyph_width, glyph_height);
+            for c in missing {
+                eprintln!("warning: no glyph for {c:?}");
+            }
+            if width == 0 {
+                bail!("there is no text to render");
+            }
+
+            output.image(&args.outfile, &pixels, width)?;
+        }
+        Command::Convert {
+            columns,
+            glyph_width,
+            glyph_height,
+            num_chars,
+        } => {
+            let mut strip = load_glyphs(&args.infile, glyph_width, glyph_height, args.key_color)?;
+            if let Some(n) = num_chars {
+                strip.resize(n * glyph_width * glyph_height, 0);
+            }
+
+            if is_json(&args.outfile) {
+                let json = json::to_json(&strip, glyph_width, glyph_height, None, None);
+                output.write(&args.outfile, json)?;
+                return Ok(());
+            }
+
+            let (out, width) = match columns {
+                Some(c) => (
+                    strip_to_grid(&strip, c, glyph_width, glyph_height),
+                    c * glyph_width as u32,
+                ),
+                None => (strip, glyph_width as u32),
+            };
+
+            output.image(&args.outfile, &out, width)?;
         }
     }
 
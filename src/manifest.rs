@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Fonts to build in one go, read from TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub font: Vec<Font>,
+}
+
+/// One `[[font]]` of a manifest: the arguments of a `build`, with paths relative to the
+/// manifest
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Font {
+    pub image: PathBuf,
+    pub output: PathBuf,
+    pub first_label: String,
+    pub second_label: String,
+    #[serde(default)]
+    pub matching: bool,
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub extra: Vec<PathBuf>,
+    /// Any other build options, passed as they are, so their paths are relative to the working
+    /// directory
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<Manifest> {
+    toml::from_str(text).context("bad manifest")
+}
@@ -0,0 +1,335 @@
+use crate::PixelFormat;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    Zero,
+    At,
+    V0,
+    V1,
+    A0,
+    A1,
+    A2,
+    A3,
+    T0,
+    T1,
+    T2,
+    T3,
+    T4,
+    T5,
+    T6,
+    T7,
+    S0,
+    S1,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    T8,
+    T9,
+    K0,
+    K1,
+    Gp,
+    Sp,
+    Fp,
+    Ra,
+}
+
+impl Reg {
+    /// Every register, by number
+    const ALL: [Reg; 32] = [
+        Reg::Zero,
+        Reg::At,
+        Reg::V0,
+        Reg::V1,
+        Reg::A0,
+        Reg::A1,
+        Reg::A2,
+        Reg::A3,
+        Reg::T0,
+        Reg::T1,
+        Reg::T2,
+        Reg::T3,
+        Reg::T4,
+        Reg::T5,
+        Reg::T6,
+        Reg::T7,
+        Reg::S0,
+        Reg::S1,
+        Reg::S2,
+        Reg::S3,
+        Reg::S4,
+        Reg::S5,
+        Reg::S6,
+        Reg::S7,
+        Reg::T8,
+        Reg::T9,
+        Reg::K0,
+        Reg::K1,
+        Reg::Gp,
+        Reg::Sp,
+        Reg::Fp,
+        Reg::Ra,
+    ];
+
+    const NAMES: [&str; 32] = [
+        "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+        "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp",
+        "fp", "ra",
+    ];
+
+    pub fn from_number(n: u32) -> Option<Self> {
+        Self::ALL.get(n as usize).copied()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix('$').unwrap_or(name);
+        if let Ok(n) = name.parse() {
+            return Self::from_number(n);
+        }
+        // s8 is another name for fp
+        if name == "s8" {
+            return Some(Reg::Fp);
+        }
+
+        Self::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// The register as written in assembly: by number, as `$16`, with `numeric`, or by name
+    pub fn syntax(self, numeric: bool) -> String {
+        match numeric {
+            true => format!("${}", self as u32),
+            false => self.name().to_string(),
+        }
+    }
+
+    /// Whether a C callee has to preserve the register
+    pub fn is_saved(self) -> bool {
+        matches!(
+            self,
+            Reg::S0
+                | Reg::S1
+                | Reg::S2
+                | Reg::S3
+                | Reg::S4
+                | Reg::S5
+                | Reg::S6
+                | Reg::S7
+                | Reg::Gp
+                | Reg::Sp
+                | Reg::Fp
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    Lw(Reg, i16, Reg),
+    /// Halfword load, for tables of offsets
+    Lhu(Reg, i16, Reg),
+    Sh(Reg, i16, Reg),
+    Sw(Reg, i16, Reg),
+    /// Doubleword store, for pairs of 32-bit pixels
+    Sd(Reg, i16, Reg),
+    Addi(Reg, Reg, i16),
+    Addiu(Reg, Reg, i16),
+    /// rd, rs, rt
+    Addu(Reg, Reg, Reg),
+    Jr(Reg),
+    /// Jump within the current 256MB region; holds the low 28 bits of the target
+    J(u32),
+    Nop,
+}
+
+impl Instr {
+    pub fn encode(self) -> u32 {
+        fn i_type(op: u32, rs: Reg, rt: Reg, imm: i16) -> u32 {
+            (op << 26) | ((rs as u32) << 21) | ((rt as u32) << 16) | (imm as u16 as u32)
+        }
+
+        match self {
+            Instr::Lw(rt, offset, base) => i_type(0x23, base, rt, offset),
+            Instr::Lhu(rt, offset, base) => i_type(0x25, base, rt, offset),
+            Instr::Sh(rt, offset, base) => i_type(0x29, base, rt, offset),
+            Instr::Sw(rt, offset, base) => i_type(0x2B, base, rt, offset),
+            Instr::Sd(rt, offset, base) => i_type(0x3F, base, rt, offset),
+            Instr::Addi(rt, rs, imm) => i_type(0x08, rs, rt, imm),
+            Instr::Addiu(rt, rs, imm) => i_type(0x09, rs, rt, imm),
+            Instr::Addu(rd, rs, rt) => {
+                ((rs as u32) << 21) | ((rt as u32) << 16) | ((rd as u32) << 11) | 0x21
+            }
+            Instr::Jr(rs) => ((rs as u32) << 21) | 0x08,
+            Instr::J(target) => (0x02 << 26) | ((target >> 2) & 0x03FFFFFF),
+            Instr::Nop => 0,
+        }
+    }
+
+    pub fn decode(word: u32) -> Option<Self> {
+        if word == 0 {
+            return Some(Instr::Nop);
+        }
+
+        let rs = Reg::from_number((word >> 21) & 0x1F);
+        let rt = Reg::from_number((word >> 16) & 0x1F);
+        let rd = Reg::from_number((word >> 11) & 0x1F);
+        let imm = word as u16 as i16;
+
+        Some(match word >> 26 {
+            0x00 if word & 0x001FFFFF == 0x08 => Instr::Jr(rs?),
+            0x00 if word & 0x7FF == 0x21 => Instr::Addu(rd?, rs?, rt?),
+            0x02 => Instr::J((word & 0x03FFFFFF) << 2),
+            0x08 => Instr::Addi(rt?, rs?, imm),
+            0x09 => Instr::Addiu(rt?, rs?, imm),
+            0x23 => Instr::Lw(rt?, imm, rs?),
+            0x25 => Instr::Lhu(rt?, imm, rs?),
+            0x29 => Instr::Sh(rt?, imm, rs?),
+            0x2B => Instr::Sw(rt?, imm, rs?),
+            0x3F => Instr::Sd(rt?, imm, rs?),
+            _ => return None,
+        })
+    }
+
+    /// Parse one line of assembly as written by [`body_source`]
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (op, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+
+        let reg = |i: usize| Reg::from_name(args.get(i)?);
+        let imm = |i: usize| -> Option<i16> { args.get(i)?.parse().ok() };
+        let mem = |i: usize| -> Option<(i16, Reg)> {
+            let (offset, base) = args.get(i)?.strip_suffix(')')?.split_once('(')?;
+            let offset = if offset.is_empty() {
+                0
+            } else {
+                offset.parse().ok()?
+            };
+            Some((offset, Reg::from_name(base)?))
+        };
+
+        Some(match op {
+            "lw" => {
+                let (offset, base) = mem(1)?;
+                Instr::Lw(reg(0)?, offset, base)
+            }
+            "lhu" => {
+                let (offset, base) = mem(1)?;
+                Instr::Lhu(reg(0)?, offset, base)
+            }
+            "sh" => {
+                let (offset, base) = mem(1)?;
+                Instr::Sh(reg(0)?, offset, base)
+            }
+            "sw" => {
+                let (offset, base) = mem(1)?;
+                Instr::Sw(reg(0)?, offset, base)
+            }
+            "sd" => {
+                let (offset, base) = mem(1)?;
+                Instr::Sd(reg(0)?, offset, base)
+            }
+            "addi" => Instr::Addi(reg(0)?, reg(1)?, imm(2)?),
+            "addiu" => Instr::Addiu(reg(0)?, reg(1)?, imm(2)?),
+            "addu" => Instr::Addu(reg(0)?, reg(1)?, reg(2)?),
+            "jr" => Instr::Jr(reg(0)?),
+            "nop" => Instr::Nop,
+            _ => return None,
+        })
+    }
+
+    pub fn is_jump(self) -> bool {
+        matches!(self, Instr::Jr(_) | Instr::J(_))
+    }
+
+    /// Pixels of `format` written by this instruction to the framebuffer in `framebuffer`
+    pub fn pixels(self, format: PixelFormat, framebuffer: Reg) -> usize {
+        let bytes = match self {
+            Instr::Sh(_, _, base) if base == framebuffer => 2,
+            Instr::Sw(_, _, base) if base == framebuffer => 4,
+            Instr::Sd(_, _, base) if base == framebuffer => 8,
+            _ => 0,
+        };
+        bytes / format.size()
+    }
+}
+
+impl Display for Instr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let width = f.width().unwrap_or(6);
+        // the alternate form writes registers by number
+        let numeric = f.alternate();
+        let reg = |r: Reg| r.syntax(numeric);
+        match *self {
+            Instr::Lw(rt, offset, base) => {
+                write!(f, "{:width$} {}, {}({})", "lw", reg(rt), offset, reg(base))
+            }
+            Instr::Lhu(rt, offset, base) => {
+                write!(f, "{:width$} {}, {}({})", "lhu", reg(rt), offset, reg(base))
+            }
+            Instr::Sh(rt, offset, base) => {
+                write!(f, "{:width$} {}, {}({})", "sh", reg(rt), offset, reg(base))
+            }
+            Instr::Sw(rt, offset, base) => {
+                write!(f, "{:width$} {}, {}({})", "sw", reg(rt), offset, reg(base))
+            }
+            Instr::Sd(rt, offset, base) => {
+                write!(f, "{:width$} {}, {}({})", "sd", reg(rt), offset, reg(base))
+            }
+            Instr::Addi(rt, rs, imm) => {
+                write!(f, "{:width$} {}, {}, {}", "addi", reg(rt), reg(rs), imm)
+            }
+            Instr::Addiu(rt, rs, imm) => {
+                write!(f, "{:width$} {}, {}, {}", "addiu", reg(rt), reg(rs), imm)
+            }
+            Instr::Addu(rd, rs, rt) => {
+                write!(f, "{:width$} {}, {}, {}", "addu", reg(rd), reg(rs), reg(rt))
+            }
+            Instr::Jr(rs) => write!(f, "{:width$} {}", "jr", reg(rs)),
+            Instr::J(target) => write!(f, "{:width$} 0x{target:07X}", "j"),
+            Instr::Nop => write!(f, "nop"),
+        }
+    }
+}
+
+/// Machine code for `row_end.s`, popping `color` and then `next` as pushed by the caller
+pub const fn row_end(color: Reg, next: Reg) -> [Instr; 6] {
+    [
+        Instr::Lw(color, 0, Reg::Sp),
+        Instr::Addi(Reg::Sp, Reg::Sp, 4),
+        Instr::Lw(next, 0, Reg::Sp),
+        Instr::Addi(Reg::Sp, Reg::Sp, 4),
+        Instr::Jr(Reg::Ra),
+        Instr::Nop,
+    ]
+}
+
+/// Machine code for the built-in `row_end.s`
+pub const ROW_END: [Instr; 6] = row_end(Reg::S1, Reg::S0);
+
+/// Source of the instructions, delay slots indented a space further, with registers by number
+/// if `numeric`
+pub fn body_source(body: &[Instr], numeric: bool) -> String {
+    let mut rv = String::new();
+    let mut delay_slot = false;
+
+    for instr in body {
+        let (indent, width) = if delay_slot {
+            ("     ", 5)
+        } else {
+            ("    ", 6)
+        };
+        rv += &match numeric {
+            true => format!("{indent}{instr:#width$}\n"),
+            false => format!("{indent}{instr:width$}\n"),
+        };
+        delay_slot = instr.is_jump();
+    }
+
+    rv
+}
@@ -0,0 +1,30 @@
+use anyhow::{Result, bail};
+
+/// Layout of the font table in a particular firmware version
+pub struct Preset {
+    pub name: &'static str,
+    /// Bytes between framebuffer rows
+    pub stride: i16,
+    /// Whether the firmware's hand patches apply
+    pub matching: bool,
+}
+
+pub const PRESETS: &[Preset] = &[Preset {
+    name: "gs-v3.3",
+    stride: 640 * 2,
+    matching: true,
+}];
+
+pub fn find(name: &str) -> Result<&'static Preset> {
+    match PRESETS.iter().find(|p| p.name == name) {
+        Some(p) => Ok(p),
+        None => bail!(
+            "unknown preset {name:?} (known: {})",
+            PRESETS
+                .iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
@@ -0,0 +1,200 @@
+use crate::charmap::Charmap;
+use anyhow::{Context, Result, bail};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODESEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_STARTSEQ: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HEADER_SIZE: u32 = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_STARTSEQ: u8 = 0xFE;
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("the PSF header is truncated")?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Glyphs of `width` pixels packed into whole bytes per row, leftmost pixel in the top bit
+fn unpack(data: &[u8], width: usize) -> Vec<u8> {
+    let row_size = width.div_ceil(8);
+    data.chunks_exact(row_size)
+        .flat_map(|row| (0..width).map(move |x| row[x / 8] >> (7 - x % 8) & 1))
+        .map(|bit| if bit != 0 { 0xFF } else { 0 })
+        .collect()
+}
+
+fn pack(glyph: &[u8], width: usize) -> Vec<u8> {
+    let mut rv = vec![];
+    for row in glyph.chunks_exact(width) {
+        for byte in row.chunks(8) {
+            rv.push(
+                byte.iter()
+                    .enumerate()
+                    .fold(0, |b, (i, &p)| b | ((p != 0) as u8) << (7 - i)),
+            );
+        }
+    }
+    rv
+}
+
+/// The glyphs of a PSF1 or PSF2 font in order, and the charmap of the first character of each
+/// glyph in its Unicode table, if it has one
+pub fn decode(
+    data: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Result<(Vec<u8>, Option<Charmap>)> {
+    let (width, height, count, glyphs, table) = if data.starts_with(&PSF1_MAGIC) {
+        let [mode, height] = *data.get(2..4).context("the PSF1 header is truncated")? else {
+            unreachable!();
+        };
+        let count = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+        let start = 4;
+        let has_table = mode & (PSF1_MODEHASTAB | PSF1_MODESEQ) != 0;
+        (8, height as usize, count, start, has_table)
+    } else if data.starts_with(&PSF2_MAGIC) {
+        let header_size = u32_at(data, 8)? as usize;
+        let flags = u32_at(data, 12)?;
+        let count = u32_at(data, 16)? as usize;
+        let height = u32_at(data, 24)? as usize;
+        let width = u32_at(data, 28)? as usize;
+        let has_table = flags & PSF2_HAS_UNICODE_TABLE != 0;
+        (width, height, count, header_size, has_table)
+    } else {
+        bail!("not a PSF1 or PSF2 font");
+    };
+
+    if (width, height) != (glyph_width, glyph_height) {
+        bail!(
+            "the font's glyphs are {width}x{height}, not {glyph_width}x{glyph_height}; pass \
+             --glyph-width and --glyph-height to match"
+        );
+    }
+
+    let glyph_size = width.div_ceil(8) * height;
+    let end = glyphs + count * glyph_size;
+    let bitmaps = data
+        .get(glyphs..end)
+        .with_context(|| format!("the font is too short for {count} glyphs"))?;
+    let font = unpack(bitmaps, width);
+
+    if !table {
+        return Ok((font, None));
+    }
+
+    // the first character of each glyph; the rest, and any sequences, are alternatives
+    let mut entries = vec![];
+    let table = &data[end..];
+    if data.starts_with(&PSF1_MAGIC) {
+        let codes = table
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        let mut glyph = 0;
+        let mut first = true;
+        let mut sequence = false;
+        for code in codes {
+            match code {
+                PSF1_SEPARATOR => (glyph, first, sequence) = (glyph + 1, true, false),
+                PSF1_STARTSEQ => sequence = true,
+                _ if first && !sequence => {
+                    if let Some(c) = char::from_u32(code.into()) {
+                        entries.push((glyph, c));
+                        first = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+    } else {
+        for (glyph, entry) in table.split(|&b| b == PSF2_SEPARATOR).enumerate() {
+            let singles = entry
+                .split(|&b| b == PSF2_STARTSEQ)
+                .next()
+                .unwrap_or_default();
+            let singles = String::from_utf8_lossy(singles);
+            if let Some(c) = singles.chars().next() {
+                entries.push((glyph, c));
+            }
+        }
+    }
+
+    Ok((font, Some(Charmap::from_entries(entries))))
+}
+
+/// A PSF2 font, or with `psf1` a PSF1 font padded out to 256 or 512 glyphs, with a Unicode
+/// table giving each glyph its character in `charmap`
+pub fn encode(
+    glyphs: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+    charmap: &Charmap,
+    psf1: bool,
+) -> Result<Vec<u8>> {
+    let glyphs = glyphs
+        .chunks_exact(glyph_width * glyph_height)
+        .collect::<Vec<_>>();
+    let mut rv = vec![];
+
+    if psf1 {
+        if glyph_width != 8 || glyph_height > u8::MAX as usize {
+            bail!("PSF1 fonts are 8 pixels wide and at most 255 tall");
+        }
+        let count = match glyphs.len() {
+            ..=256 => 256,
+            257..=512 => 512,
+            n => bail!("PSF1 fonts have at most 512 glyphs, not {n}"),
+        };
+
+        let mode = if count == 512 { PSF1_MODE512 } else { 0 } | PSF1_MODEHASTAB;
+        rv.extend(PSF1_MAGIC);
+        rv.extend([mode, glyph_height as u8]);
+        for glyph in &glyphs {
+            rv.extend(pack(glyph, glyph_width));
+        }
+        rv.resize(4 + count * glyph_height, 0);
+
+        for index in 0..count {
+            // PSF1 tables only hold the Basic Multilingual Plane
+            if let Some(c) = charmap
+                .char(index)
+                .filter(|&c| (c as u32) < PSF1_STARTSEQ.into())
+            {
+                rv.extend((c as u16).to_le_bytes());
+            }
+            rv.extend(PSF1_SEPARATOR.to_le_bytes());
+        }
+    } else {
+        let glyph_size = glyph_width.div_ceil(8) * glyph_height;
+        rv.extend(PSF2_MAGIC);
+        for field in [
+            0,
+            PSF2_HEADER_SIZE,
+            PSF2_HAS_UNICODE_TABLE,
+            glyphs.len() as u32,
+            glyph_size as u32,
+            glyph_height as u32,
+            glyph_width as u32,
+        ] {
+            rv.extend(field.to_le_bytes());
+        }
+        for glyph in &glyphs {
+            rv.extend(pack(glyph, glyph_width));
+        }
+
+        for index in 0..glyphs.len() {
+            if let Some(c) = charmap.char(index) {
+                rv.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            rv.push(PSF2_SEPARATOR);
+        }
+    }
+
+    Ok(rv)
+}
@@ -0,0 +1,288 @@
+use crate::mips::Instr;
+use crate::{
+    ExtractOptions, PixelFormat, Registers, build_function, extract, infer_extra_offset,
+    infer_num_chars,
+};
+use anyhow::{Context, Result, bail};
+use byteorder::{BE, ByteOrder};
+
+const BUNDLED: &str = include_str!("quirks.txt");
+
+/// A hand edit to one generated row function
+#[derive(Clone)]
+pub struct Quirk {
+    pub name: String,
+    pub row: u8,
+    pub double: bool,
+    /// Pixel pair whose first store is replaced
+    pub pair: u32,
+    pub instr: Instr,
+}
+
+impl Quirk {
+    pub fn function(&self) -> String {
+        let kind = if self.double { "double" } else { "single" };
+        format!("row_{kind}_{:08b}", self.row)
+    }
+
+    /// Whether its row, `glyph_width` pixels wide, stores the pair it replaces the store of
+    pub fn has_store(&self, glyph_width: usize) -> bool {
+        let shift = glyph_width as u32 - 2 * self.pair - 2;
+        (u32::from(self.row) >> shift) & 0b11 != 0
+    }
+}
+
+/// Everything known to be odd about one firmware's table
+#[derive(Clone, Default)]
+pub struct Firmware {
+    pub name: String,
+    pub quirks: Vec<Quirk>,
+    /// Functions emitted first, in this order
+    pub order: Vec<String>,
+}
+
+/// Sections start with `[name]`; anything before the first one belongs to an unnamed firmware
+pub fn parse(text: &str) -> Result<Vec<Firmware>> {
+    let mut rv = vec![Firmware::default()];
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            rv.push(Firmware {
+                name: name.trim().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let context = || format!("quirks line {}", number + 1);
+        // there's always at least the unnamed section
+        let firmware = rv.last_mut().unwrap();
+
+        let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match directive {
+            "patch" => {
+                let mut fields = rest.split_whitespace();
+                let mut field = || fields.next().with_context(context);
+                let name = field()?.to_string();
+                let double = match field()? {
+                    "single" => false,
+                    "double" => true,
+                    kind => bail!("{}: expected single or double, not {kind:?}", context()),
+                };
+                let row = u8::from_str_radix(field()?, 2).with_context(context)?;
+                let pair = field()?.parse().with_context(context)?;
+                let instr = fields.collect::<Vec<_>>().join(" ");
+                let Some(instr) = Instr::parse(&instr) else {
+                    bail!("{}: can't parse instruction {instr:?}", context());
+                };
+
+                if pair >= u8::BITS / 2 {
+                    bail!(
+                        "{}: there are only {} pairs in a row",
+                        context(),
+                        u8::BITS / 2
+                    );
+                }
+
+                firmware.quirks.push(Quirk {
+                    name,
+                    row,
+                    double,
+                    pair,
+                    instr,
+                });
+            }
+            "order" => firmware
+                .order
+                .extend(rest.split_whitespace().map(str::to_string)),
+            _ => bail!("{}: unknown directive {directive:?}", context()),
+        }
+    }
+
+    Ok(rv)
+}
+
+pub fn bundled() -> Vec<Firmware> {
+    parse(BUNDLED).expect("bundled quirks are valid")
+}
+
+/// The oddities of the firmware a preset names
+pub fn firmware(preset: &str) -> Firmware {
+    firmware_in(&bundled(), preset)
+}
+
+/// The oddities `database`, such as a parsed patch file, lists for the firmware a preset names
+pub fn firmware_in(database: &[Firmware], preset: &str) -> Firmware {
+    database
+        .iter()
+        .find(|f| f.name == preset)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Every known quirk, across all firmware
+pub fn all() -> Vec<Quirk> {
+    all_in(&bundled())
+}
+
+/// Every quirk in `database`, across all firmware
+pub fn all_in(database: &[Firmware]) -> Vec<Quirk> {
+    database.iter().flat_map(|f| f.quirks.clone()).collect()
+}
+
+pub fn find(name: &str) -> Result<Quirk> {
+    find_in(&bundled(), name)
+}
+
+pub fn find_in(database: &[Firmware], name: &str) -> Result<Quirk> {
+    let all = all_in(database);
+    match all.iter().find(|q| q.name == name) {
+        Some(q) => Ok(q.clone()),
+        None => bail!(
+            "unknown quirk {name:?} (known: {})",
+            all.iter()
+                .map(|q| q.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Patches in the format of `quirks.txt`, with no firmware section
+pub fn source(quirks: &[Quirk]) -> String {
+    let mut rv = String::from("# patch <name> <single|double> <row> <pair> <instruction>\n");
+    for q in quirks {
+        let kind = if q.double { "double" } else { "single" };
+        rv += &format!(
+            "patch {} {kind} {:08b} {} {:1}\n",
+            q.name, q.row, q.pair, q.instr
+        );
+    }
+    rv
+}
+
+/// The patches that make the generated row functions match those of the 8-pixel-wide table at
+/// the start of `target`, linked at `vram`, and a note for each function that differs in a way
+/// no patch can express
+pub fn derive(
+    target: &[u8],
+    vram: u32,
+    glyph_height: usize,
+    stride: i16,
+    registers: Registers,
+) -> Result<(Vec<Quirk>, Vec<String>)> {
+    let num_chars = infer_num_chars(target, vram, glyph_height, true, false, registers)?;
+    let mut options = ExtractOptions {
+        vram,
+        num_chars,
+        first: 0,
+        count: num_chars,
+        extra_offset: 0,
+        glyph_width: 8,
+        glyph_height,
+        row_end: true,
+        pixel_format: PixelFormat::Rgba16,
+        registers,
+    };
+    options.extra_offset = infer_extra_offset(target, &options)?;
+    let extraction = extract(target, &options)?;
+
+    let mut rv: Vec<Quirk> = vec![];
+    let mut notes = vec![];
+    let mut seen = vec![];
+    for f in &extraction.functions {
+        let Some((double, row)) = f
+            .name
+            .strip_prefix("row_single_")
+            .map(|row| (false, row))
+            .or_else(|| f.name.strip_prefix("row_double_").map(|row| (true, row)))
+        else {
+            continue;
+        };
+        let Ok(row) = u8::from_str_radix(row, 2) else {
+            continue;
+        };
+        // a function and its copy in the extra lines take the same patches
+        if seen.contains(&f.name) {
+            continue;
+        }
+        seen.push(f.name.clone());
+
+        let start = (f.address - vram) as usize;
+        let words = target[start..start + f.size as usize].chunks_exact(size_of::<u32>());
+        let Some(body) = words
+            .map(|w| Instr::decode(BE::read_u32(w)))
+            .collect::<Option<Vec<_>>>()
+        else {
+            notes.push(format!(
+                "{} at 0x{:08X} has instructions gsfont doesn't know",
+                f.name, f.address
+            ));
+            continue;
+        };
+
+        let build = |quirks: &[Quirk]| {
+            let format = PixelFormat::Rgba16;
+            build_function(row.into(), 8, double, format, stride, quirks, registers)
+        };
+        let generated = build(&[]);
+        if body == generated {
+            continue;
+        }
+
+        // find the pair whose first store each differing instruction replaces
+        let mut derived = vec![];
+        if body.len() == generated.len() {
+            for (index, &instr) in body.iter().enumerate() {
+                if instr == generated[index] {
+                    continue;
+                }
+                let patch = (0..u8::BITS / 2)
+                    .map(|pair| Quirk {
+                        name: String::new(),
+                        row,
+                        double,
+                        pair,
+                        instr,
+                    })
+                    .find(|q| {
+                        let patched = build(std::slice::from_ref(q));
+                        patched[index] == instr
+                            && patched
+                                .iter()
+                                .zip(&generated)
+                                .filter(|(a, b)| a != b)
+                                .count()
+                                == 1
+                    });
+                derived.extend(patch);
+            }
+        }
+        if build(&derived) != body {
+            notes.push(format!(
+                "{} at 0x{:08X} differs from the generated code in more than the first stores \
+                 of its pixel pairs, which patches can't express",
+                f.name, f.address
+            ));
+            continue;
+        }
+
+        for mut quirk in derived {
+            let kind = if double { "double" } else { "single" };
+            let instr = format!("{:1}", quirk.instr);
+            let mnemonic = instr.split_whitespace().next().unwrap_or_default();
+            quirk.name = format!("{kind}-{row:08b}-{mnemonic}");
+            if rv.iter().any(|q| q.name == quirk.name) {
+                quirk.name += &format!("-{}", quirk.pair);
+            }
+            rv.push(quirk);
+        }
+    }
+
+    Ok((rv, notes))
+}
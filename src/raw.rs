@@ -0,0 +1,68 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+
+/// Which bit of a byte holds the leftmost pixel
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BitOrder {
+    Msb,
+    Lsb,
+}
+
+/// Where each bitplane of a glyph's row is stored
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PlaneLayout {
+    /// Every plane of a row before the next row (Game Boy)
+    Interleaved,
+    /// Every row of a plane before the next plane (NES)
+    Sequential,
+}
+
+/// How glyphs are packed in a raw dump: one byte per 8 pixels per plane per row
+#[derive(Clone, Copy)]
+pub struct Format {
+    pub bit_order: BitOrder,
+    pub planes: usize,
+    pub layout: PlaneLayout,
+}
+
+/// Unpack a raw dump into `glyph_width`-pixel glyph rows; a pixel is set if any plane sets it
+pub fn decode(
+    data: &[u8],
+    format: Format,
+    glyph_width: usize,
+    glyph_height: usize,
+) -> Result<Vec<u8>> {
+    let row_size = glyph_width / 8;
+    let glyph_size = format.planes * glyph_height * row_size;
+    if !data.len().is_multiple_of(glyph_size) {
+        bail!(
+            "{} bytes isn't a whole number of {glyph_size}-byte glyphs",
+            data.len()
+        );
+    }
+
+    let mut rv = Vec::with_capacity(data.len() / format.planes * 8);
+
+    for glyph in data.chunks_exact(glyph_size) {
+        for y in 0..glyph_height {
+            for column in 0..row_size {
+                let row = (0..format.planes)
+                    .map(|plane| match format.layout {
+                        PlaneLayout::Interleaved => (y * format.planes + plane) * row_size,
+                        PlaneLayout::Sequential => (plane * glyph_height + y) * row_size,
+                    })
+                    .fold(0, |row, start| row | glyph[start + column]);
+
+                for x in 0..8 {
+                    let bit = match format.bit_order {
+                        BitOrder::Msb => 7 - x,
+                        BitOrder::Lsb => x,
+                    };
+                    rv.push(if row >> bit & 1 != 0 { 0xFF } else { 0 });
+                }
+            }
+        }
+    }
+
+    Ok(rv)
+}
@@ -0,0 +1,92 @@
+use crate::Extraction;
+
+pub fn svg(pixels: &[u8], width: u32, scale: u32) -> String {
+    let height = pixels.len() as u32 / width;
+    let mut rv = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {width} {height}\" shape-rendering=\"crispEdges\">",
+        width * scale,
+        height * scale
+    );
+
+    for (y, row) in pixels.chunks_exact(width as usize).enumerate() {
+        for (x, &p) in row.iter().enumerate() {
+            if p != 0 {
+                let v = 0xFF - p;
+                rv += &format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"1\" height=\"1\" fill=\"#{v:02x}{v:02x}{v:02x}\"/>"
+                );
+            }
+        }
+    }
+
+    rv += "</svg>";
+    rv
+}
+
+pub fn glyph_svg(glyph: &[u8], glyph_width: usize, scale: u32) -> String {
+    svg(glyph, glyph_width as u32, scale)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn html(extraction: &Extraction) -> String {
+    let w = extraction.glyph_width;
+    let mut rv = String::new();
+
+    rv += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>gsfont</title>\n";
+    rv += "<style>\n";
+    rv += "body { font-family: sans-serif; }\n";
+    rv += "table { border-collapse: collapse; }\n";
+    rv += "td, th { border: 1px solid #ccc; padding: 4px 8px; vertical-align: top; }\n";
+    rv += "svg { background: #fff; outline: 1px solid #eee; }\n";
+    rv += ".rows, .functions { font-family: monospace; white-space: pre; }\n";
+    rv += "</style>\n</head>\n<body>\n";
+    rv += &format!(
+        "<p>{} glyphs, {} extra rows</p>\n",
+        extraction.font.len() / (w * extraction.glyph_height),
+        extraction.extra.len() / w
+    );
+    rv += "<table>\n<tr><th>Index</th><th>Label</th><th>Glyph</th><th>Rows</th><th>Row functions</th></tr>\n";
+
+    let block = extraction.glyph_height + usize::from(extraction.row_end);
+    for (index, glyph) in extraction
+        .font
+        .chunks_exact(w * extraction.glyph_height)
+        .enumerate()
+    {
+        let label = match char::from_u32((extraction.first + index) as u32) {
+            Some(c) if c.is_ascii_graphic() => c.to_string(),
+            _ => String::new(),
+        };
+
+        let rows = glyph
+            .chunks_exact(w)
+            .map(|row| row.iter().fold(0, |b, i| (b << 1) | (*i != 0) as u32))
+            .map(|b| format!("{b:0w$b}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let start = index * block * 2;
+        let functions = (start..start + block * 2)
+            .filter_map(|i| Some((extraction.table.get(i)?, extraction.addresses.get(i)?)))
+            .map(|(name, address)| format!("0x{address:08X} {name}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        rv += &format!(
+            "<tr><td>0x{:02X}</td><td>{}</td><td>{}</td><td class=\"rows\">{rows}</td><td class=\"functions\">{}</td></tr>\n",
+            extraction.first + index,
+            escape(&label),
+            glyph_svg(glyph, w, 8),
+            escape(&functions)
+        );
+    }
+
+    rv += "</table>\n</body>\n</html>\n";
+    rv
+}
@@ -0,0 +1,44 @@
+use clap::ValueEnum;
+
+/// How a dump orders the bytes of each big-endian word
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ByteOrder {
+    /// As the console sees it (.z64)
+    Big,
+    /// Bytes swapped within each halfword (.v64)
+    Byteswapped,
+    /// Bytes reversed within each word (.n64)
+    Little,
+}
+
+/// The first word of an N64 ROM header, as a .z64 stores it
+const MAGIC: [u8; 4] = [0x80, 0x37, 0x12, 0x40];
+
+impl ByteOrder {
+    /// The order of a dump starting with an N64 ROM header, if it does
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let magic = data.get(..4)?;
+        [Self::Big, Self::Byteswapped, Self::Little]
+            .into_iter()
+            .find(|order| {
+                let mut word = MAGIC;
+                order.normalize(&mut word);
+                word == magic
+            })
+    }
+
+    /// Reorder `data` between big-endian and this order, which is its own inverse; a trailing
+    /// partial word is left as it is
+    pub fn normalize(self, data: &mut [u8]) {
+        for word in data.chunks_exact_mut(4) {
+            match self {
+                Self::Big => {}
+                Self::Byteswapped => {
+                    word.swap(0, 1);
+                    word.swap(2, 3);
+                }
+                Self::Little => word.reverse(),
+            }
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::mips::{Instr, Reg};
+use crate::{BuildOptions, ExtractOptions, Registers, assemble, extract, glyph_rows, layout};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+const VRAM: u32 = 0x80000000;
+const FRAMEBUFFER: u32 = 0xA0000000;
+const STACK: u32 = 0xB0000000;
+/// Return address of the simulated caller
+const RETURN: u32 = 0xC0000000;
+
+/// Where a glyph stopped surviving the build/simulate/extract round trip
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The build itself was rejected
+    Build(String),
+    /// Drawing the glyph ran something other than the generated code
+    Fault {
+        glyph: usize,
+        double: bool,
+        address: u32,
+    },
+    /// Drawing the glyph with the generated code produced different pixels
+    Render {
+        glyph: usize,
+        double: bool,
+        row: usize,
+        width: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// Extracting the generated binary produced a different glyph
+    Extract {
+        glyph: usize,
+        row: usize,
+        width: usize,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let kind = |double: bool| if double { "double" } else { "single" };
+        match *self {
+            Mismatch::Build(ref error) => write!(f, "build failed: {error}"),
+            Mismatch::Fault {
+                glyph,
+                double,
+                address,
+            } => write!(
+                f,
+                "glyph 0x{glyph:02X} ({}) faulted at 0x{address:08X}",
+                kind(double)
+            ),
+            Mismatch::Render {
+                glyph,
+                double,
+                row,
+                width: w,
+                expected,
+                found,
+            } => write!(
+                f,
+                "glyph 0x{glyph:02X} ({}) row {row} draws {found:0w$b} instead of {expected:0w$b}",
+                kind(double)
+            ),
+            Mismatch::Extract {
+                glyph,
+                row,
+                width: w,
+                expected,
+                found,
+            } => write!(
+                f,
+                "glyph 0x{glyph:02X} row {row} extracts as {found:0w$b} instead of {expected:0w$b}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+struct Machine<'a> {
+    binary: &'a [u8],
+    regs: [u32; 32],
+    memory: HashMap<u32, u8>,
+}
+
+impl Machine<'_> {
+    fn bytes<const N: usize>(&self, address: u32) -> Option<[u8; N]> {
+        if let Some(offset) = address.checked_sub(VRAM)
+            && let Some(bytes) = self.binary.get(offset as usize..offset as usize + N)
+        {
+            return Some(bytes.try_into().unwrap());
+        }
+
+        let bytes = (0..N as u32)
+            .map(|i| self.memory.get(&(address + i)).copied())
+            .collect::<Option<Vec<_>>>()?;
+        Some(bytes.try_into().unwrap())
+    }
+
+    fn load(&self, address: u32) -> Option<u32> {
+        self.bytes(address).map(u32::from_be_bytes)
+    }
+
+    fn store(&mut self, address: u32, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.memory.insert(address + i as u32, *b);
+        }
+    }
+
+    fn reg(&self, reg: Reg) -> u32 {
+        self.regs[reg as usize]
+    }
+
+    fn set(&mut self, reg: Reg, value: u32) {
+        self.regs[reg as usize] = value;
+    }
+
+    /// Returns the jump target, if any
+    fn execute(&mut self, instr: Instr, pc: u32) -> Result<Option<u32>, u32> {
+        let address = |base: u32, offset: i16| base.wrapping_add(offset as i32 as u32);
+
+        match instr {
+            Instr::Lw(rt, offset, base) => {
+                let value = self.load(address(self.reg(base), offset)).ok_or(pc)?;
+                self.set(rt, value);
+            }
+            Instr::Lhu(rt, offset, base) => {
+                let value = self.bytes(address(self.reg(base), offset)).ok_or(pc)?;
+                self.set(rt, u16::from_be_bytes(value).into());
+            }
+            Instr::Sh(rt, offset, base) => {
+                let value = self.reg(rt) as u16;
+                self.store(address(self.reg(base), offset), &value.to_be_bytes());
+            }
+            Instr::Sw(rt, offset, base) => {
+                let value = self.reg(rt);
+                self.store(address(self.reg(base), offset), &value.to_be_bytes());
+            }
+            // registers hold sign-extended 32-bit values, as after a 32-bit load
+            Instr::Sd(rt, offset, base) => {
+                let value = self.reg(rt) as i32 as i64;
+                self.store(address(self.reg(base), offset), &value.to_be_bytes());
+            }
+            Instr::Addi(rt, rs, imm) | Instr::Addiu(rt, rs, imm) => {
+                self.set(rt, address(self.reg(rs), imm));
+            }
+            Instr::Addu(rd, rs, rt) => self.set(rd, self.reg(rs).wrapping_add(self.reg(rt))),
+            Instr::Jr(rs) => return Ok(Some(self.reg(rs))),
+            Instr::J(target) => return Ok(Some(((pc + 4) & 0xF0000000) | target)),
+            Instr::Nop => {}
+        }
+
+        Ok(None)
+    }
+
+    fn fetch(&self, pc: u32) -> Result<Instr, u32> {
+        self.load(pc).and_then(Instr::decode).ok_or(pc)
+    }
+
+    /// Draw the table block at `block` the way the firmware does, returning the row bits
+    fn draw(binary: &[u8], block: u32, options: &BuildOptions) -> Result<Vec<u32>, u32> {
+        let mut machine = Machine {
+            binary,
+            regs: [0; 32],
+            memory: HashMap::new(),
+        };
+
+        let Registers {
+            list,
+            framebuffer,
+            color,
+            base,
+            ..
+        } = options.registers;
+        machine.set(Reg::Sp, STACK);
+        machine.set(Reg::Ra, RETURN);
+        machine.set(list, block);
+        machine.set(framebuffer, FRAMEBUFFER);
+        machine.set(color, u32::MAX);
+
+        // the caller pushes the next and color registers, then jumps to the first row function
+        machine.store(STACK - 4, &0u32.to_be_bytes());
+        machine.store(STACK - 8, &u32::MAX.to_be_bytes());
+        machine.set(Reg::Sp, STACK - 8);
+        let mut pc = match base {
+            Some(base) => {
+                machine.set(base, VRAM);
+                let offset = machine.bytes(block).ok_or(block)?;
+                VRAM + u32::from(u16::from_be_bytes(offset))
+            }
+            None => machine.load(block).ok_or(block)?,
+        };
+        machine.set(list, block + options.registers.entry_size() as u32);
+
+        // generous, since a row is a dozen instructions
+        for _ in 0..1000 {
+            let instr = machine.fetch(pc)?;
+            if let Some(target) = machine.execute(instr, pc)? {
+                let delay = machine.fetch(pc + 4)?;
+                if machine.execute(delay, pc + 4)?.is_some() {
+                    return Err(pc + 4);
+                }
+                if target == RETURN {
+                    return Ok(machine.rows(options));
+                }
+                pc = target;
+            } else {
+                pc += 4;
+            }
+        }
+
+        Err(pc)
+    }
+
+    fn rows(&self, options: &BuildOptions) -> Vec<u32> {
+        let mut rv = vec![0; options.glyph_height];
+
+        let size = options.pixel_format.size() as u32;
+
+        for (y, row) in rv.iter_mut().enumerate() {
+            for x in 0..options.glyph_width as u32 {
+                let address = FRAMEBUFFER + y as u32 * options.stride as u32 + x * size;
+                let set =
+                    (0..size).any(|i| self.memory.get(&(address + i)).is_some_and(|&b| b != 0));
+                *row = (*row << 1) | set as u32;
+            }
+        }
+
+        rv
+    }
+}
+
+/// Draw the single or double block of `glyph` from a binary assembled at [`VRAM`]
+fn draw_glyph(
+    binary: &[u8],
+    glyph: usize,
+    double: bool,
+    options: &BuildOptions,
+) -> Result<Vec<u32>, Mismatch> {
+    let block_size = ((options.glyph_height + 1) * options.registers.entry_size()) as u32;
+    let block = VRAM + (glyph as u32 * 2 + double as u32) * block_size;
+    Machine::draw(binary, block, options).map_err(|address| Mismatch::Fault {
+        glyph,
+        double,
+        address,
+    })
+}
+
+/// Build `glyphs` and draw every glyph with the generated code, returning the pixels its single
+/// and its double block draw as two fonts
+pub fn render(glyphs: &[u8], options: &BuildOptions) -> Result<(Vec<u8>, Vec<u8>), Mismatch> {
+    let (functions, table) = layout(glyphs, options).map_err(|e| Mismatch::Build(e.to_string()))?;
+    let binary = assemble(&functions, &table, VRAM, options.registers)
+        .map_err(|e| Mismatch::Build(e.to_string()))?;
+    let width = options.glyph_width;
+    let num_chars = glyphs.len() / (width * options.glyph_height);
+
+    let mut rv = (vec![], vec![]);
+    for glyph in 0..num_chars {
+        for (double, font) in [(false, &mut rv.0), (true, &mut rv.1)] {
+            for row in draw_glyph(&binary, glyph, double, options)? {
+                font.extend(
+                    (0..width)
+                        .rev()
+                        .map(|x| if row >> x & 1 != 0 { 0xFF } else { 0 }),
+                );
+            }
+        }
+    }
+
+    Ok(rv)
+}
+
+/// Build `glyphs`, draw every glyph with the generated code and extract it again, checking
+/// that each step reproduces the input
+pub fn roundtrip(glyphs: &[u8], options: &BuildOptions) -> Result<(), Mismatch> {
+    let (functions, table) = layout(glyphs, options).map_err(|e| Mismatch::Build(e.to_string()))?;
+    let binary = assemble(&functions, &table, VRAM, options.registers)
+        .map_err(|e| Mismatch::Build(e.to_string()))?;
+    let (width, height) = (options.glyph_width, options.glyph_height);
+    let expected = glyph_rows(glyphs, width, height);
+
+    for (glyph, rows) in expected.iter().enumerate() {
+        for double in [false, true] {
+            let found = draw_glyph(&binary, glyph, double, options)?;
+
+            if let Some(row) = (0..height).find(|&row| found[row] != rows[row]) {
+                return Err(Mismatch::Render {
+                    glyph,
+                    double,
+                    row,
+                    width,
+                    expected: rows[row],
+                    found: found[row],
+                });
+            }
+        }
+    }
+
+    // the extras are already covered by the glyphs that use them, so skip them entirely
+    let code_len = binary.len() - table.len() * options.registers.entry_size();
+    let options = ExtractOptions {
+        vram: VRAM,
+        num_chars: expected.len(),
+        first: 0,
+        count: expected.len(),
+        extra_offset: code_len,
+        glyph_width: width,
+        glyph_height: height,
+        row_end: true,
+        pixel_format: options.pixel_format,
+        registers: options.registers,
+    };
+    let extraction = extract(&binary, &options).map_err(|e| Mismatch::Build(e.to_string()))?;
+    for (glyph, (rows, found)) in expected
+        .iter()
+        .zip(glyph_rows(&extraction.font, width, height))
+        .enumerate()
+    {
+        if let Some(row) = (0..height).find(|&row| found[row] != rows[row]) {
+            return Err(Mismatch::Extract {
+                glyph,
+                row,
+                width,
+                expected: rows[row],
+                found: found[row],
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtraOrder, Framing, PixelFormat, Syntax};
+
+    /// Glyphs from an xorshift generator, so every run checks the same ones; `density` of every
+    /// 256 pixels are set
+    fn random_glyphs(seed: u32, density: u32, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state & 0xFF < density { 0xFF } else { 0 }
+            })
+            .collect()
+    }
+
+    fn options(
+        glyph_width: usize,
+        glyph_height: usize,
+        pixel_format: PixelFormat,
+        registers: Registers,
+    ) -> BuildOptions<'static> {
+        BuildOptions {
+            first_label: "font_single",
+            second_label: "font_double",
+            extra: &[],
+            quirks: &[],
+            incbin: None,
+            order: None,
+            extra_order: ExtraOrder::FirstSeen,
+            glyph_labels: &[],
+            glyph_comments: &[],
+            glyph_width,
+            glyph_height,
+            pixel_format,
+            stride: pixel_format.default_stride(),
+            entry: false,
+            glyph_entries: &[],
+            glyph_colors: None,
+            shared: &[],
+            extra_layout: None,
+            framing: Framing::default(),
+            registers,
+            syntax: Syntax::default(),
+        }
+    }
+
+    fn check(options: &BuildOptions) {
+        let size = options.glyph_width * options.glyph_height;
+        for seed in 1..=16 {
+            for density in [32, 128, 224] {
+                let glyphs = random_glyphs(seed, density, 32 * size);
+                if let Err(mismatch) = roundtrip(&glyphs, options) {
+                    panic!("seed {seed}, density {density}: {mismatch}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_glyphs_survive() {
+        check(&options(8, 8, PixelFormat::Rgba16, Registers::default()));
+        check(&options(8, 8, PixelFormat::Rgba32, Registers::default()));
+    }
+
+    #[test]
+    fn double_height_glyphs_survive() {
+        check(&options(8, 16, PixelFormat::Rgba16, Registers::default()));
+        check(&options(16, 16, PixelFormat::Rgba16, Registers::default()));
+    }
+
+    #[test]
+    fn custom_registers_survive() {
+        let registers = Registers {
+            list: Reg::T0,
+            framebuffer: Reg::T1,
+            color: Reg::T2,
+            next: Reg::T3,
+            base: None,
+        };
+        check(&options(8, 8, PixelFormat::Rgba16, registers));
+        check(&options(8, 16, PixelFormat::Rgba32, registers));
+    }
+
+    #[test]
+    fn half_tables_survive() {
+        let registers = Registers {
+            base: Some(Reg::T4),
+            ..Registers::default()
+        };
+        check(&options(8, 8, PixelFormat::Rgba16, registers));
+        check(&options(8, 16, PixelFormat::Rgba16, registers));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::mips::{self, Instr};
+use crate::{ExtractOptions, Parsed, PixelFormat, Registers, infer_extra_offset, parse_function};
+use byteorder::{BE, ByteOrder};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// A run of words that looks like a font table
+pub struct Candidate {
+    pub vram: u32,
+    pub num_chars: usize,
+    /// Bytes from the end of the table to the extra lines, if the code follows the table
+    pub extra_offset: Option<usize>,
+}
+
+/// Look for font tables in `data`, loaded at `base`: runs of blocks of `glyph_height` pointers
+/// to row functions, which start `lw s0, 0(a0); addi a0, a0, 4` in the default `registers`,
+/// each ending in a pointer to `row_end`
+pub fn scan(
+    data: &[u8],
+    base: u32,
+    glyph_width: usize,
+    glyph_height: usize,
+    pixel_format: PixelFormat,
+    registers: Registers,
+) -> Vec<Candidate> {
+    let Registers {
+        list, color, next, ..
+    } = registers;
+    let words = data
+        .chunks_exact(size_of::<u32>())
+        .map(BE::read_u32)
+        .collect::<Vec<_>>();
+    let address = |index: usize| base.wrapping_add((index * size_of::<u32>()) as u32);
+
+    let prologue = [
+        Instr::Lw(next, 0, list).encode(),
+        Instr::Addi(list, list, size_of::<u32>() as i16).encode(),
+    ];
+    let row_end = mips::row_end(color, next);
+    let epilogue = [row_end[0].encode(), row_end[1].encode()];
+
+    // whether each function is row_end, by address
+    let mut functions = HashMap::new();
+    for (index, pair) in words.windows(2).enumerate() {
+        if pair != prologue && pair != epilogue {
+            continue;
+        }
+        let mut cursor = Cursor::new(data);
+        cursor.set_position((index * size_of::<u32>()) as u64);
+        match parse_function(&mut cursor, base, glyph_width, pixel_format, registers) {
+            Ok(Parsed::Row(..)) => functions.insert(address(index), false),
+            Ok(Parsed::End) => functions.insert(address(index), true),
+            _ => None,
+        };
+    }
+
+    let block = glyph_height + 1;
+    let is_block = |start: usize| {
+        words.get(start..start + block).is_some_and(|b| {
+            b[..glyph_height]
+                .iter()
+                .all(|w| functions.get(w) == Some(&false))
+                && functions.get(&b[glyph_height]) == Some(&true)
+        })
+    };
+
+    let mut rv = vec![];
+    let mut index = 0;
+    while index < words.len() {
+        if !is_block(index) {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        while is_block(index) {
+            index += block;
+        }
+        let num_chars = (index - start) / (block * 2);
+        if num_chars == 0 {
+            continue;
+        }
+
+        let vram = address(start);
+        let table = &words[start..start + num_chars * block * 2];
+        let code = address(start + table.len());
+        let extra_offset = match table.iter().min() {
+            Some(&lowest) if lowest == code => {
+                let options = ExtractOptions {
+                    vram,
+                    num_chars,
+                    first: 0,
+                    count: num_chars,
+                    extra_offset: 0,
+                    glyph_width,
+                    glyph_height,
+                    row_end: true,
+                    pixel_format,
+                    registers,
+                };
+                infer_extra_offset(&data[start * size_of::<u32>()..], &options).ok()
+            }
+            _ => None,
+        };
+
+        rv.push(Candidate {
+            vram,
+            num_chars,
+            extra_offset,
+        });
+    }
+
+    rv
+}
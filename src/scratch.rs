@@ -0,0 +1,106 @@
+use crate::mips::Instr;
+use crate::{
+    Parsed, PixelFormat, Registers, Shades, expected_shades, parse_function, row_name, show_shades,
+};
+use anyhow::{Result, bail};
+use byteorder::{BE, ByteOrder};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// The fields of a decomp.me scratch, as its API creates one from
+#[derive(Serialize)]
+pub struct Scratch {
+    pub name: String,
+    pub platform: &'static str,
+    pub compiler: String,
+    pub compiler_flags: String,
+    pub diff_label: String,
+    pub target_asm: String,
+    pub context: String,
+    pub source_code: String,
+}
+
+/// A scratch of the row function or `row_end` at the start of `code`, linked at `address`, with
+/// its machine code as the target and its register conventions and pixels as the context; the
+/// function is named after the row it draws unless `name` is given
+pub fn export(
+    code: &[u8],
+    address: u32,
+    name: Option<&str>,
+    glyph_width: usize,
+    pixel_format: PixelFormat,
+    registers: Registers,
+    (compiler, compiler_flags): (&str, &str),
+) -> Result<String> {
+    let mut cursor = Cursor::new(code);
+    let parsed = parse_function(&mut cursor, address, glyph_width, pixel_format, registers)?;
+    let size = cursor.position() as usize;
+
+    let (default_name, pixels) = match parsed {
+        Parsed::Row(pixels, _) => {
+            let row = pixels.iter().fold(0, |b, p| (b << 1) | (*p != 0) as u32);
+            let kind = match pixels.contains(&Shades::default().pair) {
+                true => "double",
+                false => "single",
+            };
+            (row_name(kind, row, glyph_width), Some((row, kind, pixels)))
+        }
+        Parsed::End => ("row_end".to_string(), None),
+        Parsed::Unknown => bail!("0x{address:08X} isn't a row function or row_end"),
+    };
+    let name = name.map_or(default_name, str::to_string);
+
+    let mut target_asm = format!("glabel {name}\n");
+    for (offset, word) in code[..size].chunks_exact(size_of::<u32>()).enumerate() {
+        let word = BE::read_u32(word);
+        let instr = match Instr::decode(word) {
+            Some(instr) => format!("{instr:1}"),
+            None => "?".to_string(),
+        };
+        target_asm += &format!(
+            "/* {:08X} */ .word 0x{word:08X} /* {instr} */\n",
+            address as usize + offset * size_of::<u32>()
+        );
+    }
+
+    let Registers {
+        list,
+        framebuffer,
+        color,
+        next,
+        ..
+    } = registers;
+    let mut context = format!(
+        "/* {name} at 0x{address:08X}, {size} bytes; the table pointer is in {}, the \
+         framebuffer in {}, the color in {} and the next function in {} */\n",
+        list.syntax(false),
+        framebuffer.syntax(false),
+        color.syntax(false),
+        next.syntax(false)
+    );
+    if let Some((row, kind, pixels)) = pixels {
+        let expected = expected_shades(row, glyph_width, kind == "double");
+        context += &format!("/* stores {} */\n", show_shades(&pixels));
+        if *pixels != expected[..] {
+            context += &format!(
+                "/* the generated code stores {}, so this one is patched */\n",
+                show_shades(&expected)
+            );
+        }
+    }
+    context += &format!("void {name}(void);\n");
+
+    let scratch = Scratch {
+        source_code: format!("void {name}(void) {{\n}}\n"),
+        diff_label: name.clone(),
+        name,
+        platform: "n64",
+        compiler: compiler.to_string(),
+        compiler_flags: compiler_flags.to_string(),
+        target_asm,
+        context,
+    };
+
+    // serde_json can't fail on these types
+    Ok(serde_json::to_string_pretty(&scratch).unwrap() + "\n")
+}
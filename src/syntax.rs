@@ -0,0 +1,224 @@
+use crate::mips::{self, Instr, Reg};
+use crate::{EPILOGUE, PROLOGUE, Registers};
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+
+/// Assembler the generated source is written for
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Assembler {
+    /// GNU as, through the LEAF, END, EXPORT, PUSH and POP macros of asm.h
+    #[default]
+    Gas,
+    /// armips, with plain labels and the macros written out
+    Armips,
+    /// bass, with plain labels, its own data directives and line comments
+    Bass,
+}
+
+/// Macros that GNU as output defines its functions and labels with, after the conventions of
+/// different projects
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum MacroStyle {
+    /// LEAF, END and EXPORT from asm.h
+    #[default]
+    Leaf,
+    /// splat's glabel, endlabel, dlabel and jlabel from macro.inc
+    Splat,
+    /// glabel for functions and data alike, with nothing ending a function
+    Glabel,
+}
+
+/// How the generated assembly is written, for assemblers and projects with other conventions
+#[derive(Clone, Copy, Default)]
+pub struct Syntax {
+    pub assembler: Assembler,
+    /// Write registers by number, as `$16`, instead of by name
+    pub numeric_registers: bool,
+    pub macro_style: MacroStyle,
+    /// Mark functions with `.type`, `.ent`, `.end` and `.size`, for disassemblers and debuggers
+    pub function_directives: bool,
+}
+
+impl Syntax {
+    pub fn reg(self, reg: Reg) -> String {
+        reg.syntax(self.numeric_registers)
+    }
+
+    pub fn body(self, body: &[Instr]) -> String {
+        mips::body_source(body, self.numeric_registers)
+    }
+
+    /// `instr` in the delay slot of a jump written out by hand
+    pub fn delay_slot(self, instr: Instr) -> String {
+        match self.numeric_registers {
+            true => format!("     {instr:#5}\n"),
+            false => format!("     {instr:5}\n"),
+        }
+    }
+
+    /// Check the assembler can read the source as configured
+    pub fn check(self) -> Result<()> {
+        if self.assembler == Assembler::Bass && self.numeric_registers {
+            bail!("bass reads $16 as a hex number, so it needs registers by name");
+        }
+        if self.assembler != Assembler::Gas && self.macro_style != MacroStyle::Leaf {
+            bail!("macro styles are for GNU as; armips and bass output uses plain labels");
+        }
+        if self.assembler != Assembler::Gas && self.function_directives {
+            bail!("function directives are for GNU as");
+        }
+        Ok(())
+    }
+
+    /// Whether the macros of asm.h, LEAF, END, PUSH and POP, are available
+    fn asm_h(self) -> bool {
+        self.assembler == Assembler::Gas && self.macro_style == MacroStyle::Leaf
+    }
+
+    /// Start of a function called `name`
+    pub fn leaf(self, name: &str) -> String {
+        let mut rv = String::new();
+        if self.function_directives {
+            rv += &format!("    .type {name}, @function\n");
+            // LEAF gives the function its .ent already
+            if !self.asm_h() {
+                rv += &format!("    .ent {name}\n");
+            }
+        }
+        rv + &match (self.assembler, self.macro_style) {
+            (Assembler::Gas, MacroStyle::Leaf) => format!("LEAF({name})\n"),
+            (Assembler::Gas, MacroStyle::Splat | MacroStyle::Glabel) => format!("glabel {name}\n"),
+            (Assembler::Armips | Assembler::Bass, _) => format!("{name}:\n"),
+        }
+    }
+
+    /// End of the function called `name`
+    pub fn end(self, name: &str) -> String {
+        let rv = match (self.assembler, self.macro_style) {
+            (Assembler::Gas, MacroStyle::Leaf) => format!("END({name})\n"),
+            (Assembler::Gas, MacroStyle::Splat) => format!("endlabel {name}\n"),
+            (Assembler::Gas, MacroStyle::Glabel) | (Assembler::Armips | Assembler::Bass, _) => {
+                String::new()
+            }
+        };
+        if !self.function_directives {
+            return rv;
+        }
+        // END gives it its .end already
+        match self.asm_h() {
+            true => rv + &format!("    .size {name}, . - {name}\n"),
+            false => rv + &format!("    .end {name}\n    .size {name}, . - {name}\n"),
+        }
+    }
+
+    /// A global data label
+    pub fn export(self, label: &str) -> String {
+        match (self.assembler, self.macro_style) {
+            (Assembler::Gas, MacroStyle::Leaf) => format!("EXPORT({label})\n"),
+            (Assembler::Gas, MacroStyle::Splat) => format!("dlabel {label}\n"),
+            (Assembler::Gas, MacroStyle::Glabel) => format!("glabel {label}\n"),
+            (Assembler::Armips | Assembler::Bass, _) => format!("{label}:\n"),
+        }
+    }
+
+    /// A label within a function, for jumping into the middle of it
+    pub fn label(self, label: &str) -> String {
+        match (self.assembler, self.macro_style) {
+            (Assembler::Gas, MacroStyle::Splat) => format!("jlabel {label}\n"),
+            _ => format!("{label}:\n"),
+        }
+    }
+
+    pub fn comment(self, text: &str) -> String {
+        match self.assembler {
+            Assembler::Gas | Assembler::Armips => format!("/* {text} */\n"),
+            Assembler::Bass => format!("// {text}\n"),
+        }
+    }
+
+    /// A 32-bit data word holding `value`
+    pub fn word(self, value: &str) -> String {
+        match self.assembler {
+            Assembler::Gas | Assembler::Armips => format!("    .word {value}\n"),
+            Assembler::Bass => format!("    dd {value}\n"),
+        }
+    }
+
+    /// A 16-bit data halfword holding `value`
+    pub fn half(self, value: &str) -> String {
+        match self.assembler {
+            Assembler::Gas | Assembler::Armips => format!("    .half {value}\n"),
+            Assembler::Bass => format!("    dw {value}\n"),
+        }
+    }
+
+    /// The contents of the file at `path`
+    pub fn incbin(self, path: &str) -> String {
+        match self.assembler {
+            Assembler::Gas | Assembler::Armips => format!("    .incbin \"{path}\"\n"),
+            Assembler::Bass => format!("    insert \"{path}\"\n"),
+        }
+    }
+
+    /// Save `reg` on the stack
+    pub fn push(self, reg: Reg) -> String {
+        match self.asm_h() {
+            true => format!("    PUSH({})\n", self.reg(reg)),
+            false => self.body(&[
+                Instr::Addi(Reg::Sp, Reg::Sp, -(size_of::<u32>() as i16)),
+                Instr::Sw(reg, 0, Reg::Sp),
+            ]),
+        }
+    }
+
+    /// Restore `reg` from the stack
+    pub fn pop(self, reg: Reg) -> String {
+        match self.asm_h() {
+            true => format!("    POP({})\n", self.reg(reg)),
+            false => self.body(&[
+                Instr::Lw(reg, 0, Reg::Sp),
+                Instr::Addi(Reg::Sp, Reg::Sp, size_of::<u32>() as i16),
+            ]),
+        }
+    }
+
+    /// Start of the file, before the tables; armips and bass output is placed at `org` if given
+    pub fn prologue(self, org: Option<u32>) -> String {
+        let (arch, org) = match self.assembler {
+            Assembler::Gas if self.asm_h() => return PROLOGUE.to_string(),
+            Assembler::Gas => {
+                return PROLOGUE.replace("#include \"asm.h\"", ".include \"macro.inc\"");
+            }
+            Assembler::Armips => (".n64", org.map(|org| format!(".org 0x{org:08X}"))),
+            Assembler::Bass => (
+                "arch n64.cpu\nendian msb",
+                org.map(|org| format!("base 0x{org:08X}")),
+            ),
+        };
+
+        let mut rv = format!("{arch}\n\n");
+        if let Some(org) = org {
+            rv += &format!("{org}\n\n");
+        }
+        rv
+    }
+
+    /// End of the file, after the functions
+    pub fn epilogue(self) -> String {
+        match self.assembler {
+            Assembler::Gas => EPILOGUE.to_string(),
+            Assembler::Armips | Assembler::Bass => String::new(),
+        }
+    }
+
+    /// Source of `row_end`, popping the registers the caller saved
+    pub fn row_end(self, registers: Registers) -> String {
+        let Registers { color, next, .. } = registers;
+        let mut rv = self.leaf("row_end");
+        rv += &self.pop(color);
+        rv += &self.pop(next);
+        rv += &self.body(&[Instr::Jr(Reg::Ra), Instr::Nop]);
+        rv += &self.end("row_end");
+        rv + "\n"
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct State {
+    /// When the last phase ended
+    last: Instant,
+    /// Total time of each phase, in the order they first ended
+    totals: Vec<(&'static str, Duration)>,
+    /// Nesting depth of [`suspend`] guards; laps inside them count towards the enclosing phase
+    suspended: usize,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// Start timing phases from now on
+pub fn enable() {
+    *STATE.lock().unwrap() = Some(State {
+        last: Instant::now(),
+        totals: vec![],
+        suspended: 0,
+    });
+}
+
+/// Count the time since the previous phase ended towards `name`; does nothing unless enabled
+pub fn lap(name: &'static str) {
+    let mut state = STATE.lock().unwrap();
+    let Some(state) = state.as_mut().filter(|s| s.suspended == 0) else {
+        return;
+    };
+
+    let now = Instant::now();
+    match state.totals.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, total)) => *total += now - state.last,
+        None => state.totals.push((name, now - state.last)),
+    }
+    state.last = now;
+}
+
+pub struct Suspended;
+
+impl Drop for Suspended {
+    fn drop(&mut self) {
+        if let Some(state) = STATE.lock().unwrap().as_mut() {
+            state.suspended -= 1;
+        }
+    }
+}
+
+/// Ignore laps until the guard is dropped, so a phase can reuse code that times itself
+pub fn suspend() -> Suspended {
+    if let Some(state) = STATE.lock().unwrap().as_mut() {
+        state.suspended += 1;
+    }
+    Suspended
+}
+
+/// The phase durations as a table, if timing is enabled
+pub fn report() -> Option<String> {
+    let state = STATE.lock().unwrap();
+    let totals = &state.as_ref()?.totals;
+
+    let width = totals.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    let mut rv = String::from("timings:\n");
+    for (name, total) in totals {
+        rv += &format!(
+            "    {name:width$}  {:>10.3}ms\n",
+            total.as_secs_f64() * 1000.0
+        );
+    }
+    let sum = totals.iter().map(|(_, t)| *t).sum::<Duration>();
+    rv += &format!(
+        "    {:width$}  {:>10.3}ms\n",
+        "total",
+        sum.as_secs_f64() * 1000.0
+    );
+
+    Some(rv)
+}
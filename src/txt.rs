@@ -0,0 +1,103 @@
+use crate::charmap::Charmap;
+use anyhow::{Result, bail};
+
+/// Plain-text representation of a font: each glyph is its index in hex, followed by its
+/// character if there's a charmap, then a line of `.` and `#` per row, with a blank line between
+/// glyphs
+pub fn to_txt(
+    glyphs: &[u8],
+    glyph_width: usize,
+    glyph_height: usize,
+    charmap: Option<&Charmap>,
+) -> String {
+    let mut rv = String::new();
+
+    for (index, glyph) in glyphs.chunks_exact(glyph_width * glyph_height).enumerate() {
+        if index > 0 {
+            rv += "\n";
+        }
+        rv += &format!("0x{index:02X}");
+        match charmap.and_then(|c| c.char(index)) {
+            Some(c) if c.is_whitespace() || c.is_control() => rv += &format!(" U+{:04X}", c as u32),
+            Some(c) => rv += &format!(" {c}"),
+            None => {}
+        }
+        rv += "\n";
+        for row in glyph.chunks_exact(glyph_width) {
+            rv.extend(row.iter().map(|&p| if p != 0 { '#' } else { '.' }));
+            rv += "\n";
+        }
+    }
+
+    rv
+}
+
+/// The glyphs of a text font; glyphs without an index line follow the previous glyph, and
+/// anything after the index is ignored
+pub fn from_txt(text: &str, glyph_width: usize, glyph_height: usize) -> Result<Vec<u8>> {
+    let size = glyph_width * glyph_height;
+    let mut rv = vec![];
+    let mut seen = vec![];
+    let mut index = 0;
+    let mut rows = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(hex) = line.strip_prefix("0x") {
+            let hex = hex.split_whitespace().next().unwrap_or_default();
+            if !rows.is_empty() {
+                bail!(
+                    "line {}: glyph 0x{index:02X} has {} rows instead of {glyph_height}",
+                    number + 1,
+                    rows.len() / glyph_width
+                );
+            }
+            let Ok(i) = usize::from_str_radix(hex, 16) else {
+                bail!("line {}: bad glyph index {line:?}", number + 1);
+            };
+            index = i;
+            continue;
+        }
+
+        if line.chars().count() != glyph_width {
+            bail!(
+                "line {}: row {line:?} is not {glyph_width} pixels",
+                number + 1
+            );
+        }
+        for c in line.chars() {
+            rows.push(match c {
+                '.' => 0,
+                '#' => 0xFF,
+                _ => bail!("line {}: {c:?} is not . or #", number + 1),
+            });
+        }
+        if rows.len() < size {
+            continue;
+        }
+
+        if seen.len() <= index {
+            seen.resize(index + 1, false);
+            rv.resize((index + 1) * size, 0);
+        }
+        if std::mem::replace(&mut seen[index], true) {
+            bail!("line {}: glyph 0x{index:02X} is defined twice", number + 1);
+        }
+        rv[index * size..][..size].copy_from_slice(&rows);
+        rows.clear();
+        index += 1;
+    }
+
+    if !rows.is_empty() {
+        bail!(
+            "the last glyph has {} rows instead of {glyph_height}",
+            rows.len() / glyph_width
+        );
+    }
+
+    Ok(rv)
+}